@@ -1,11 +1,47 @@
+use std::borrow::Cow;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
 use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
 
-use super::{config::Config, utils::check};
+use super::{
+    config::{Config, Proposal},
+    error::{self, check_op, Error, Operation},
+    fuel::{inject_fuel_checks, FUEL_YIELD_IMPORT, MAX_YIELD_FUEL},
+    instance::{
+        function::{FuncType, InnerFuncType},
+        global::{GlobType, InnerGlobType},
+        memory::{InnerMemType, MemType},
+        table::{InnerTableType, TableType},
+    },
+    interrupt::{inject_interrupt_checks, MAX_INTERRUPT_INTERVAL},
+    limits::table_maximums,
+    snapshot::{export_state, fingerprint, STATE_EXPORT_PREFIX},
+    trap::{inject_call_tracing, FuncNames},
+    validator::{ValidationReport, Validator},
+};
 use wasmedge_sys::ffi;
 
+/// The proposals modules transformed by [load_async_module_from_bytes](Loader::load_async_module_from_bytes)
+/// need, whatever the loader's config turns on.
+pub const ASYNCIFY_PROPOSALS: [Proposal; 3] = [
+    Proposal::MultiMemories,
+    Proposal::BulkMemoryOperations,
+    Proposal::ImportExportMutGlobals,
+];
+
 pub struct Loader {
     pub(crate) loader_inner: InnerLoader,
-    pub(crate) validator_inner: InnerValidator,
+    /// A copy of the config the loader was created with, used to create the validator on first use.
+    pub(crate) config: Option<Config>,
+    validator: OnceLock<Validator>,
+    pub(crate) keep_names: bool,
+    pub(crate) yield_fuel: Option<u32>,
+    pub(crate) interrupt_checks: Option<u32>,
+    pub(crate) snapshots: bool,
+    pub(crate) trace_calls: bool,
 }
 
 pub(crate) struct InnerLoader(pub(crate) *mut ffi::WasmEdge_LoaderContext);
@@ -32,58 +68,273 @@ impl Loader {
             if loader_inner.is_null() {
                 return Err(WasmEdgeError::LoaderCreate);
             }
+            let loader_inner = InnerLoader(loader_inner);
 
-            let validator_inner = ffi::WasmEdge_ValidatorCreate(config_ctx);
-            if validator_inner.is_null() {
-                return Err(WasmEdgeError::ValidatorCreate);
-            }
             Ok(Self {
-                loader_inner: InnerLoader(loader_inner),
-                validator_inner: InnerValidator(validator_inner),
+                loader_inner,
+                config: match config {
+                    Some(config) => Some(Config::copy_from(config)?),
+                    None => None,
+                },
+                validator: OnceLock::new(),
+                keep_names: false,
+                yield_fuel: None,
+                interrupt_checks: None,
+                snapshots: false,
+                trace_calls: false,
             })
         }
     }
-    pub fn load_module_from_bytes(&self, wasm: &[u8]) -> Result<AstModule, WasmEdgeError> {
+
+    /// Keeps the `name` section and debug info of modules transformed by
+    /// [load_async_module_from_bytes](Loader::load_async_module_from_bytes), so that
+    /// the [trap report](crate::TrapReport) of trap errors shows function names. Defaults to `false`.
+    pub fn keep_names(&mut self, enable: bool) {
+        self.keep_names = enable;
+    }
+
+    pub fn keep_names_enabled(&self) -> bool {
+        self.keep_names
+    }
+
+    /// Makes modules transformed by [load_async_module_from_bytes](Loader::load_async_module_from_bytes)
+    /// export the mutable globals and memories they do not export themselves, so that
+    /// [snapshots](crate::AsyncLinker::snapshot) capture the whole state of the module. Defaults to
+    /// `false`, which leaves the exports of the module as they are and refuses snapshots.
+    ///
+    /// See the [snapshot](super::snapshot) module for the names of the added exports.
+    pub fn snapshots(&mut self, enable: bool) {
+        self.snapshots = enable;
+    }
+
+    pub fn snapshots_enabled(&self) -> bool {
+        self.snapshots
+    }
+
+    /// Makes loaded modules, sync or async, keep a shadow stack of their running functions, so that
+    /// the [trap report](crate::TrapReport) of trap errors lists the whole guest call stack. Defaults
+    /// to `false`, which only reports the exports entered from the host.
+    ///
+    /// The calls that keep the stack count towards the statistics and the cost of every call. See
+    /// the [trap](super::trap) module for how the module is instrumented.
+    pub fn trace_calls(&mut self, enable: bool) {
+        self.trace_calls = enable;
+    }
+
+    pub fn trace_calls_enabled(&self) -> bool {
+        self.trace_calls
+    }
+
+    /// Makes modules transformed by [load_async_module_from_bytes](Loader::load_async_module_from_bytes)
+    /// yield to the async runtime every `fuel` loop iterations, so that a guest that never calls an
+    /// async import does not starve other tasks. Defaults to `None`, which never yields.
+    ///
+    /// See the [fuel](super::fuel) module for how the module is instrumented.
+    ///
+    /// # Error
+    ///
+    /// If `fuel` is larger than [MAX_YIELD_FUEL], which the guest's `i32` fuel counter cannot hold,
+    /// then an error is returned.
+    pub fn set_yield_fuel(&mut self, fuel: Option<u32>) -> WasmEdgeResult<()> {
+        if let Some(fuel) = fuel.filter(|fuel| *fuel > MAX_YIELD_FUEL) {
+            return Err(WasmEdgeError::Operation(format!(
+                "yield fuel {} is larger than {}",
+                fuel, MAX_YIELD_FUEL
+            )));
+        }
+        self.yield_fuel = fuel;
+        Ok(())
+    }
+
+    pub fn get_yield_fuel(&self) -> Option<u32> {
+        self.yield_fuel
+    }
+
+    /// Makes loaded modules, sync or async, check every `interval` loop iterations whether the
+    /// running call must be stopped, so that a guest loop can be
+    /// [interrupted](super::interrupt) or timed out. Defaults to `None`, which adds no checks.
+    ///
+    /// See the [fuel](super::fuel) module for how the module is instrumented.
+    ///
+    /// # Error
+    ///
+    /// If `interval` is larger than [MAX_INTERRUPT_INTERVAL], which the guest's `i32` counter cannot
+    /// hold, then an error is returned.
+    pub fn set_interrupt_checks(&mut self, interval: Option<u32>) -> WasmEdgeResult<()> {
+        if let Some(interval) = interval.filter(|interval| *interval > MAX_INTERRUPT_INTERVAL) {
+            return Err(WasmEdgeError::Operation(format!(
+                "interrupt check interval {} is larger than {}",
+                interval, MAX_INTERRUPT_INTERVAL
+            )));
+        }
+        self.interrupt_checks = interval;
+        Ok(())
+    }
+
+    pub fn get_interrupt_checks(&self) -> Option<u32> {
+        self.interrupt_checks
+    }
+
+    /// Adds the interrupt checks to `wasm` if they are turned on.
+    fn with_interrupt_checks<'a>(&self, wasm: &'a [u8]) -> error::Result<Cow<'a, [u8]>> {
+        match self.interrupt_checks {
+            Some(interval) => Ok(Cow::Owned(inject_interrupt_checks(wasm, interval)?)),
+            None => Ok(Cow::Borrowed(wasm)),
+        }
+    }
+
+    /// Adds the call tracing to `wasm` if it is turned on.
+    fn with_call_tracing<'a>(&self, wasm: Cow<'a, [u8]>) -> error::Result<Cow<'a, [u8]>> {
+        match self.trace_calls {
+            true => Ok(Cow::Owned(inject_call_tracing(&wasm)?)),
+            false => Ok(wasm),
+        }
+    }
+
+    /// Parses `wasm` without validating it.
+    pub fn parse(&self, wasm: &[u8]) -> error::Result<AstModule> {
         unsafe {
             let mut mod_ctx: *mut ffi::WasmEdge_ASTModuleContext = std::ptr::null_mut();
 
-            check(ffi::WasmEdge_LoaderParseFromBuffer(
-                self.loader_inner.0,
-                &mut mod_ctx,
-                wasm.as_ptr(),
-                wasm.len() as u32,
-            ))?;
+            check_op(
+                ffi::WasmEdge_LoaderParseFromBuffer(
+                    self.loader_inner.0,
+                    &mut mod_ctx,
+                    wasm.as_ptr(),
+                    wasm.len() as u32,
+                ),
+                Operation::Load,
+            )?;
 
             if mod_ctx.is_null() {
-                return Err(WasmEdgeError::ModuleCreate);
+                return Err(Error::new(Operation::Load, WasmEdgeError::ModuleCreate));
             }
-
-            check(ffi::WasmEdge_ValidatorValidate(
-                self.validator_inner.0,
-                mod_ctx,
-            ))?;
-
             Ok(AstModule {
-                inner: InnerModule(mod_ctx),
+                inner: Arc::new(InnerModule(mod_ctx)),
+                func_names: FuncNames::parse(wasm),
+                fingerprint: fingerprint(wasm),
+                table_maximums: table_maximums(wasm),
+                state_exported: false,
+                call_traced: false,
             })
         }
     }
 
+    /// Returns the validator for the config of this loader, creating it on first use.
+    pub fn validator(&self) -> error::Result<&Validator> {
+        if let Some(validator) = self.validator.get() {
+            return Ok(validator);
+        }
+        let validator =
+            Validator::create(&self.config).map_err(|e| Error::new(Operation::Validate, e))?;
+        Ok(self.validator.get_or_init(|| validator))
+    }
+
+    pub fn validate(&self, module: &AstModule) -> error::Result<()> {
+        self.validator()?.validate(module)
+    }
+
+    /// Parses and validates `wasm`, adding the [interrupt checks](Loader::set_interrupt_checks) and
+    /// the [call tracing](Loader::trace_calls) if they are turned on.
+    pub fn load_module_from_bytes(&self, wasm: &[u8]) -> error::Result<AstModule> {
+        let wasm = self.with_call_tracing(self.with_interrupt_checks(wasm)?)?;
+        let mut module = self.load_instrumented(&wasm)?;
+        module.call_traced = self.trace_calls;
+        Ok(module)
+    }
+
+    /// Parses and validates `wasm`, which is already instrumented.
+    fn load_instrumented(&self, wasm: &[u8]) -> error::Result<AstModule> {
+        let module = self.parse(wasm)?;
+        self.validate(&module)?;
+        Ok(module)
+    }
+
+    /// Parses and validates `wasm`, reporting which proposals the module needs if it fails.
+    ///
+    /// On failure, the proposals the module uses are detected with `wasmparser` and the ones the
+    /// config turns off are reported.
+    pub fn load_with_report(&self, wasm: &[u8]) -> Result<AstModule, ValidationReport> {
+        let error = match self.load_module_from_bytes(wasm) {
+            Ok(module) => return Ok(module),
+            Err(error) => error,
+        };
+        let enabled = match &self.config {
+            Some(config) => config.enabled_proposals(),
+            None => Config::create()
+                .map(|config| config.enabled_proposals())
+                .unwrap_or_default(),
+        };
+        let required = match error.operation() {
+            Operation::Load | Operation::Validate => Self::required_proposals(wasm, &enabled),
+            _ => vec![],
+        };
+        Err(ValidationReport {
+            error,
+            required,
+            enabled,
+        })
+    }
+
+    /// Returns the proposals `wasm` uses that are not in `enabled`, see [Proposal::detect].
+    fn required_proposals(wasm: &[u8], enabled: &[Proposal]) -> Vec<Proposal> {
+        Proposal::detect(wasm)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|proposal| !enabled.contains(proposal))
+            .collect()
+    }
+
+    /// Returns a loader like this one with `proposals` turned on as well, or `None` if they already are.
+    fn with_proposals(&self, proposals: &[Proposal]) -> WasmEdgeResult<Option<Loader>> {
+        let mut config = match &self.config {
+            Some(config) => Config::copy_from(config)?,
+            None => Config::create()?,
+        };
+        if proposals
+            .iter()
+            .all(|proposal| config.proposal_enabled(*proposal))
+        {
+            return Ok(None);
+        }
+        for proposal in proposals {
+            config.proposal(*proposal, true);
+        }
+        let mut loader = Loader::create(&Some(config))?;
+        loader.keep_names = self.keep_names;
+        Ok(Some(loader))
+    }
+
     pub fn load_async_module_from_bytes(
         &self,
         wasm: &[u8],
         async_fn_names: &[&str],
-    ) -> Result<AstModule, WasmEdgeError> {
+    ) -> error::Result<AstModule> {
         let mut codegen_config = binaryen::CodegenConfig::default();
         codegen_config.optimization_level = 2;
-        // codegen_config.debug_info = true;
+        codegen_config.debug_info = self.keep_names;
+
+        let mut async_fn_names = async_fn_names.to_vec();
+        // like the fuel checks, the interrupt checks go in before the transform
+        let checked = self.with_interrupt_checks(wasm)?;
+        let wasm = &checked[..];
+        let fueled;
+        let wasm = match self.yield_fuel {
+            Some(fuel) => {
+                fueled = inject_fuel_checks(wasm, fuel)?;
+                async_fn_names.push(FUEL_YIELD_IMPORT);
+                &fueled[..]
+            }
+            None => wasm,
+        };
 
         let async_fn_name = async_fn_names.join(",");
         codegen_config
             .pass_argument
             .push(("asyncify-imports".to_string(), async_fn_name));
 
-        let mut module = binaryen::Module::read(wasm).map_err(|_| WasmEdgeError::ModuleCreate)?;
+        let mut module = binaryen::Module::read(wasm)
+            .map_err(|_| Error::new(Operation::Load, WasmEdgeError::ModuleCreate))?;
 
         // pass start
         {
@@ -99,35 +350,294 @@ impl Loader {
             }
         }
 
+        let mut passes = vec!["asyncify"];
+        if !self.keep_names {
+            passes.push("strip");
+        }
         module
-            .run_optimization_passes(["asyncify", "strip"], &codegen_config)
-            .map_err(|_| WasmEdgeError::ModuleCreate)?;
+            .run_optimization_passes(passes, &codegen_config)
+            .map_err(|_| Error::new(Operation::Asyncify, WasmEdgeError::ModuleCreate))?;
 
-        let new_wasm = module.write();
-        self.load_module_from_bytes(&new_wasm)
+        let mut new_wasm = module.write();
+        // snapshots can only read the mutable state the module exports
+        if self.snapshots {
+            new_wasm = export_state(&new_wasm)?;
+        }
+        // the tracing goes in last, so that unwinding and rewinding leave and re-enter the frames
+        let new_wasm = self.with_call_tracing(Cow::Owned(new_wasm))?;
+        // the transform keeps unwound stacks in a second memory and exports mutable globals
+        let mut module = match self
+            .with_proposals(&ASYNCIFY_PROPOSALS)
+            .map_err(|e| Error::new(Operation::Load, e))?
+        {
+            Some(loader) => loader.load_instrumented(&new_wasm),
+            None => self.load_instrumented(&new_wasm),
+        }?;
+        module.state_exported = self.snapshots;
+        module.call_traced = self.trace_calls;
+        Ok(module)
     }
-}
 
-pub(crate) struct InnerValidator(pub(crate) *mut ffi::WasmEdge_ValidatorContext);
-impl Drop for InnerValidator {
-    fn drop(&mut self) {
-        if !self.0.is_null() {
-            unsafe { ffi::WasmEdge_ValidatorDelete(self.0) }
-        }
+    pub fn load_from_file<P: AsRef<Path>>(&self, path: P) -> error::Result<AstModule> {
+        self.load_module_from_bytes(&read_file(path.as_ref())?)
+    }
+
+    pub fn load_from_reader<R: Read>(&self, reader: R) -> error::Result<AstModule> {
+        self.load_module_from_bytes(&read_all(reader)?)
+    }
+
+    /// Loads a module from the WebAssembly text format.
+    pub fn load_from_wat(&self, wat: &str) -> error::Result<AstModule> {
+        self.load_module_from_bytes(&parse_wat(wat)?)
+    }
+
+    /// Loads the module at `path` with the asyncify transform, see
+    /// [load_async_module_from_bytes](Loader::load_async_module_from_bytes).
+    pub fn load_async_from_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        async_fn_names: &[&str],
+    ) -> error::Result<AstModule> {
+        self.load_async_module_from_bytes(&read_file(path.as_ref())?, async_fn_names)
+    }
+
+    pub fn load_async_from_reader<R: Read>(
+        &self,
+        reader: R,
+        async_fn_names: &[&str],
+    ) -> error::Result<AstModule> {
+        self.load_async_module_from_bytes(&read_all(reader)?, async_fn_names)
+    }
+
+    pub fn load_async_from_wat(
+        &self,
+        wat: &str,
+        async_fn_names: &[&str],
+    ) -> error::Result<AstModule> {
+        self.load_async_module_from_bytes(&parse_wat(wat)?, async_fn_names)
     }
 }
-unsafe impl Send for InnerValidator {}
-unsafe impl Sync for InnerValidator {}
 
-#[derive(Debug)]
+fn read_file(path: &Path) -> error::Result<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| Error::new(Operation::Load, e).with_name(&path.display().to_string()))
+}
+
+fn read_all<R: Read>(mut reader: R) -> error::Result<Vec<u8>> {
+    let mut wasm = vec![];
+    reader
+        .read_to_end(&mut wasm)
+        .map_err(|e| Error::new(Operation::Load, e))?;
+    Ok(wasm)
+}
+
+fn parse_wat(wat: &str) -> error::Result<Vec<u8>> {
+    wat::parse_str(wat).map_err(|e| Error::new(Operation::Load, e))
+}
+
+/// A loaded module. Clones share the underlying WasmEdge module.
+#[derive(Debug, Clone)]
 pub struct AstModule {
-    pub(crate) inner: InnerModule,
+    pub(crate) inner: Arc<InnerModule>,
+    pub(crate) func_names: FuncNames,
+    /// Identifies the module a [Snapshot](crate::Snapshot) was taken from.
+    pub(crate) fingerprint: u64,
+    /// The maximum size each table declares, checked against
+    /// [max_table_elements](crate::ResourceLimits::max_table_elements).
+    pub(crate) table_maximums: Vec<Option<u64>>,
+    /// Whether all the mutable state of the module is exported, see [Loader::snapshots].
+    pub(crate) state_exported: bool,
+    /// Whether the module keeps a shadow stack of its running functions, see [Loader::trace_calls].
+    pub(crate) call_traced: bool,
 }
 
 impl AstModule {
-    pub fn create_from_wasm(loader: &Loader, wasm: &[u8]) -> Result<Self, WasmEdgeError> {
+    pub fn create_from_wasm(loader: &Loader, wasm: &[u8]) -> error::Result<Self> {
         loader.load_module_from_bytes(wasm)
     }
+
+    /// Returns the imports of this module, in declaration order.
+    ///
+    /// Imports of other kinds than functions, tables, memories and globals, e.g. exception tags,
+    /// are skipped.
+    pub fn imports(&self) -> Vec<ImportType> {
+        let len = unsafe { ffi::WasmEdge_ASTModuleListImportsLength(self.inner.0) };
+        let mut imports = Vec::with_capacity(len as usize);
+        unsafe {
+            ffi::WasmEdge_ASTModuleListImports(self.inner.0, imports.as_mut_ptr(), len);
+            imports.set_len(len as usize);
+        }
+
+        imports
+            .into_iter()
+            .filter_map(|import| unsafe {
+                let module: String = ffi::WasmEdge_ImportTypeGetModuleName(import).into();
+                let name: String = ffi::WasmEdge_ImportTypeGetExternalName(import).into();
+                let ty = match ffi::WasmEdge_ImportTypeGetExternalType(import) {
+                    ffi::WasmEdge_ExternalType_Function => ExternalType::Func(
+                        FuncType {
+                            inner: InnerFuncType(ffi::WasmEdge_ImportTypeGetFunctionType(
+                                self.inner.0,
+                                import,
+                            )),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Table => ExternalType::Table(
+                        TableType {
+                            inner: InnerTableType(ffi::WasmEdge_ImportTypeGetTableType(
+                                self.inner.0,
+                                import,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Memory => ExternalType::Memory(
+                        MemType {
+                            inner: InnerMemType(ffi::WasmEdge_ImportTypeGetMemoryType(
+                                self.inner.0,
+                                import,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Global => ExternalType::Global(
+                        GlobType {
+                            inner: InnerGlobType(ffi::WasmEdge_ImportTypeGetGlobalType(
+                                self.inner.0,
+                                import,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    _ => return None,
+                };
+                Some(ImportType { module, name, ty })
+            })
+            .collect()
+    }
+
+    /// Returns the exports of this module, in declaration order.
+    ///
+    /// Exports of other kinds than functions, tables, memories and globals are skipped, and so are
+    /// the exports added for [snapshots](Loader::snapshots).
+    pub fn exports(&self) -> Vec<ExportType> {
+        let len = unsafe { ffi::WasmEdge_ASTModuleListExportsLength(self.inner.0) };
+        let mut exports = Vec::with_capacity(len as usize);
+        unsafe {
+            ffi::WasmEdge_ASTModuleListExports(self.inner.0, exports.as_mut_ptr(), len);
+            exports.set_len(len as usize);
+        }
+
+        exports
+            .into_iter()
+            .filter_map(|export| unsafe {
+                let name: String = ffi::WasmEdge_ExportTypeGetExternalName(export).into();
+                if name.starts_with(STATE_EXPORT_PREFIX) {
+                    return None;
+                }
+                let ty = match ffi::WasmEdge_ExportTypeGetExternalType(export) {
+                    ffi::WasmEdge_ExternalType_Function => ExternalType::Func(
+                        FuncType {
+                            inner: InnerFuncType(ffi::WasmEdge_ExportTypeGetFunctionType(
+                                self.inner.0,
+                                export,
+                            )),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Table => ExternalType::Table(
+                        TableType {
+                            inner: InnerTableType(ffi::WasmEdge_ExportTypeGetTableType(
+                                self.inner.0,
+                                export,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Memory => ExternalType::Memory(
+                        MemType {
+                            inner: InnerMemType(ffi::WasmEdge_ExportTypeGetMemoryType(
+                                self.inner.0,
+                                export,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    ffi::WasmEdge_ExternalType_Global => ExternalType::Global(
+                        GlobType {
+                            inner: InnerGlobType(ffi::WasmEdge_ExportTypeGetGlobalType(
+                                self.inner.0,
+                                export,
+                            ) as *mut _),
+                        }
+                        .into(),
+                    ),
+                    _ => return None,
+                };
+                Some(ExportType { name, ty })
+            })
+            .collect()
+    }
+}
+
+/// Defines the type of an import or export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalType {
+    Func(wasmedge_types::FuncType),
+    Table(wasmedge_types::TableType),
+    Memory(wasmedge_types::MemoryType),
+    Global(wasmedge_types::GlobalType),
+}
+
+fn fmt_limit(f: &mut std::fmt::Formatter<'_>, min: u32, max: Option<u32>) -> std::fmt::Result {
+    match max {
+        Some(max) => write!(f, "{{min: {}, max: {}}}", min, max),
+        None => write!(f, "{{min: {}}}", min),
+    }
+}
+
+impl std::fmt::Display for ExternalType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExternalType::Func(ty) => write!(
+                f,
+                "func {:?} -> {:?}",
+                ty.args().unwrap_or_default(),
+                ty.returns().unwrap_or_default()
+            ),
+            ExternalType::Table(ty) => {
+                write!(f, "table {:?} ", ty.elem_ty())?;
+                fmt_limit(f, ty.minimum(), ty.maximum())
+            }
+            ExternalType::Memory(ty) => {
+                write!(f, "memory ")?;
+                fmt_limit(f, ty.minimum(), ty.maximum())?;
+                match ty.shared() {
+                    true => write!(f, " shared"),
+                    false => Ok(()),
+                }
+            }
+            ExternalType::Global(ty) => {
+                write!(f, "global {:?} {:?}", ty.mutability(), ty.value_ty())
+            }
+        }
+    }
+}
+
+/// Defines an import of an [AstModule](crate::AstModule).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportType {
+    pub module: String,
+    pub name: String,
+    pub ty: ExternalType,
+}
+
+/// Defines an export of an [AstModule](crate::AstModule).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportType {
+    pub name: String,
+    pub ty: ExternalType,
 }
 
 #[derive(Debug)]
@@ -141,3 +651,54 @@ impl Drop for InnerModule {
 }
 unsafe impl Send for InnerModule {}
 unsafe impl Sync for InnerModule {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_from_wat() {
+        let loader = Loader::create(&None).unwrap();
+        let module = loader
+            .load_from_wat(include_str!("../../demo.wat"))
+            .unwrap();
+
+        let mut exports = module
+            .exports()
+            .into_iter()
+            .map(|export| export.name)
+            .collect::<Vec<_>>();
+        exports.sort();
+        assert_eq!(exports, ["_start", "call_sleep1"]);
+        assert_eq!(module.imports().len(), 3);
+    }
+
+    #[test]
+    fn report_required_proposals() {
+        let loader = Loader::create(&Some(Config::create().unwrap())).unwrap();
+        let wasm = wat::parse_str("(module (memory 1) (memory 1))").unwrap();
+        let report = loader.load_with_report(&wasm).unwrap_err();
+        assert_eq!(report.required, [Proposal::MultiMemories]);
+        assert!(!report.enabled.contains(&Proposal::MultiMemories));
+    }
+
+    #[test]
+    fn exports_skip_state_exports() {
+        let loader = Loader::create(&None).unwrap();
+        assert!(!loader.snapshots_enabled());
+        let module = loader
+            .load_from_wat(
+                r#"(module
+                    (global (export "__wasmedge_global_0") (mut i32) (i32.const 0))
+                    (memory (export "memory") 1))"#,
+            )
+            .unwrap();
+        let exports = module
+            .exports()
+            .into_iter()
+            .map(|export| export.name)
+            .collect::<Vec<_>>();
+        assert_eq!(exports, ["memory"]);
+        assert!(!module.state_exported);
+    }
+}
@@ -1,8 +1,10 @@
 //! Defines WasmEdge Config struct.
 
+use serde::{Deserialize, Serialize};
 use wasmedge_sys::ffi;
 use wasmedge_types::error::WasmEdgeError;
 use wasmedge_types::WasmEdgeResult;
+use wasmparser::{Validator, WasmFeatures};
 
 /// Defines Config struct used to check/set the configuration options.
 ///
@@ -169,6 +171,8 @@ impl Config {
 
         config.memory64(src.memory64_enabled());
 
+        config.multi_memories(src.multi_memories_enabled());
+
         config.multi_value(src.multi_value_enabled());
 
         config.mutable_globals(src.mutable_globals_enabled());
@@ -213,6 +217,21 @@ impl Config {
         Ok(config)
     }
 
+    /// Creates a new [Config](crate::Config) turning on exactly the WebAssembly proposals `wasm` uses,
+    /// see [Proposal::detect].
+    ///
+    /// # Error
+    ///
+    /// If the module is invalid with every proposal WasmEdge supports on, then an error is returned.
+    pub fn for_module(wasm: &[u8]) -> WasmEdgeResult<Self> {
+        let used = Proposal::detect(wasm)?;
+        let mut config = Config::create()?;
+        for proposal in Proposal::ALL {
+            config.proposal(proposal, used.contains(&proposal));
+        }
+        Ok(config)
+    }
+
     /// Enables or disables host registration wasi.
     ///
     /// # Argument
@@ -824,9 +843,194 @@ impl Config {
     pub fn is_time_measuring(&self) -> bool {
         unsafe { ffi::WasmEdge_ConfigureStatisticsIsTimeMeasuring(self.inner.0) }
     }
+
+    /// Enables or disables a WebAssembly proposal.
+    ///
+    /// # Argument
+    ///
+    /// * `proposal` - The proposal to turn on or off.
+    ///
+    /// * `enable` - Whether the proposal turns on or not.
+    pub fn proposal(&mut self, proposal: Proposal, enable: bool) {
+        unsafe {
+            if enable {
+                ffi::WasmEdge_ConfigureAddProposal(self.inner.0, proposal.into())
+            } else {
+                ffi::WasmEdge_ConfigureRemoveProposal(self.inner.0, proposal.into())
+            }
+        }
+    }
+
+    /// Checks if a WebAssembly proposal turns on or not.
+    pub fn proposal_enabled(&self, proposal: Proposal) -> bool {
+        unsafe { ffi::WasmEdge_ConfigureHasProposal(self.inner.0, proposal.into()) }
+    }
+
+    /// Returns the WebAssembly proposals that turn on.
+    pub fn enabled_proposals(&self) -> Vec<Proposal> {
+        Proposal::ALL
+            .iter()
+            .copied()
+            .filter(|proposal| self.proposal_enabled(*proposal))
+            .collect()
+    }
+}
+
+/// Defines the WebAssembly proposals a [Config](crate::Config) can turn on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Proposal {
+    ImportExportMutGlobals,
+    NonTrapFloatToIntConversions,
+    SignExtensionOperators,
+    MultiValue,
+    BulkMemoryOperations,
+    ReferenceTypes,
+    #[serde(rename = "simd")]
+    SIMD,
+    TailCall,
+    MultiMemories,
+    Annotations,
+    Memory64,
+    Threads,
+    ExceptionHandling,
+    FunctionReferences,
+}
+
+impl Proposal {
+    pub const ALL: [Proposal; 14] = [
+        Proposal::ImportExportMutGlobals,
+        Proposal::NonTrapFloatToIntConversions,
+        Proposal::SignExtensionOperators,
+        Proposal::MultiValue,
+        Proposal::BulkMemoryOperations,
+        Proposal::ReferenceTypes,
+        Proposal::SIMD,
+        Proposal::TailCall,
+        Proposal::MultiMemories,
+        Proposal::Annotations,
+        Proposal::Memory64,
+        Proposal::Threads,
+        Proposal::ExceptionHandling,
+        Proposal::FunctionReferences,
+    ];
+
+    /// Returns the WebAssembly proposals `wasm` uses.
+    ///
+    /// The module is validated with `wasmparser`, once with the features of every proposal WasmEdge
+    /// supports and once for each proposal with only that proposal off; a proposal is used if the
+    /// module is invalid without it. Features WasmEdge has no proposal for, such as GC or the
+    /// component model, are never on.
+    ///
+    /// # Error
+    ///
+    /// If the module is invalid with every supported proposal on, then an error is returned.
+    pub fn detect(wasm: &[u8]) -> WasmEdgeResult<Vec<Proposal>> {
+        let supported = Proposal::ALL
+            .iter()
+            .fold(Proposal::BASELINE, |features, proposal| {
+                features | proposal.features()
+            });
+        let validates =
+            |features: WasmFeatures| Validator::new_with_features(features).validate_all(wasm);
+        validates(supported)
+            .map_err(|e| WasmEdgeError::Operation(format!("invalid module: {}", e)))?;
+
+        Ok(Proposal::ALL
+            .iter()
+            .copied()
+            .filter(|proposal| validates(supported - proposal.features()).is_err())
+            .collect())
+    }
+
+    /// The `wasmparser` features of the MVP, which every WasmEdge config supports.
+    const BASELINE: WasmFeatures = WasmFeatures::FLOATS.union(WasmFeatures::GC_TYPES);
+
+    /// Returns the `wasmparser` features a module using the proposal needs, along with the features
+    /// that would allow the same constructs without it.
+    fn features(self) -> WasmFeatures {
+        match self {
+            Proposal::ImportExportMutGlobals => WasmFeatures::MUTABLE_GLOBAL,
+            Proposal::NonTrapFloatToIntConversions => WasmFeatures::SATURATING_FLOAT_TO_INT,
+            Proposal::SignExtensionOperators => WasmFeatures::SIGN_EXTENSION,
+            Proposal::MultiValue => WasmFeatures::MULTI_VALUE,
+            Proposal::BulkMemoryOperations => WasmFeatures::BULK_MEMORY,
+            Proposal::ReferenceTypes => {
+                WasmFeatures::REFERENCE_TYPES | WasmFeatures::FUNCTION_REFERENCES
+            }
+            Proposal::SIMD => WasmFeatures::SIMD,
+            Proposal::TailCall => WasmFeatures::TAIL_CALL,
+            Proposal::MultiMemories => WasmFeatures::MULTI_MEMORY,
+            // annotations only exist in the text format
+            Proposal::Annotations => WasmFeatures::empty(),
+            Proposal::Memory64 => WasmFeatures::MEMORY64,
+            Proposal::Threads => WasmFeatures::THREADS,
+            Proposal::ExceptionHandling => WasmFeatures::LEGACY_EXCEPTIONS,
+            Proposal::FunctionReferences => WasmFeatures::FUNCTION_REFERENCES,
+        }
+    }
+}
+
+impl From<Proposal> for ffi::WasmEdge_Proposal {
+    fn from(proposal: Proposal) -> Self {
+        match proposal {
+            Proposal::ImportExportMutGlobals => ffi::WasmEdge_Proposal_ImportExportMutGlobals,
+            Proposal::NonTrapFloatToIntConversions => {
+                ffi::WasmEdge_Proposal_NonTrapFloatToIntConversions
+            }
+            Proposal::SignExtensionOperators => ffi::WasmEdge_Proposal_SignExtensionOperators,
+            Proposal::MultiValue => ffi::WasmEdge_Proposal_MultiValue,
+            Proposal::BulkMemoryOperations => ffi::WasmEdge_Proposal_BulkMemoryOperations,
+            Proposal::ReferenceTypes => ffi::WasmEdge_Proposal_ReferenceTypes,
+            Proposal::SIMD => ffi::WasmEdge_Proposal_SIMD,
+            Proposal::TailCall => ffi::WasmEdge_Proposal_TailCall,
+            Proposal::MultiMemories => ffi::WasmEdge_Proposal_MultiMemories,
+            Proposal::Annotations => ffi::WasmEdge_Proposal_Annotations,
+            Proposal::Memory64 => ffi::WasmEdge_Proposal_Memory64,
+            Proposal::Threads => ffi::WasmEdge_Proposal_Threads,
+            Proposal::ExceptionHandling => ffi::WasmEdge_Proposal_ExceptionHandling,
+            Proposal::FunctionReferences => ffi::WasmEdge_Proposal_FunctionReferences,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct InnerConfig(pub(crate) *mut ffi::WasmEdge_ConfigureContext);
 unsafe impl Send for InnerConfig {}
 unsafe impl Sync for InnerConfig {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(wat: &str) -> WasmEdgeResult<Vec<Proposal>> {
+        Proposal::detect(&wat::parse_str(wat).unwrap())
+    }
+
+    #[test]
+    fn detect_mvp_module() {
+        let used = detect("(module (memory 1) (func (export \"f\") (result i32) i32.const 1))");
+        assert_eq!(used.unwrap(), []);
+    }
+
+    #[test]
+    fn detect_bulk_memory() {
+        let used = detect(
+            r#"(module
+                (memory 1)
+                (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 8))))"#,
+        );
+        assert_eq!(used.unwrap(), [Proposal::BulkMemoryOperations]);
+    }
+
+    #[test]
+    fn detect_multi_memory() {
+        let used = detect("(module (memory 1) (memory 1))");
+        assert_eq!(used.unwrap(), [Proposal::MultiMemories]);
+    }
+
+    #[test]
+    fn detect_rejects_unsupported_features() {
+        assert!(detect("(module (type (struct (field i32))))").is_err());
+    }
+}
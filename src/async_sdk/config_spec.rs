@@ -0,0 +1,314 @@
+//! Defines ConfigSpec, a serializable description of a [Config] for loading runtime profiles from TOML
+//! or JSON.
+//!
+//! ```toml
+//! proposals = ["bulk_memory_operations", "multi_memories", "simd"]
+//! wasi = true
+//! max_memory_pages = 1024
+//!
+//! [statistics]
+//! count_instructions = true
+//! ```
+
+use serde::{Deserialize, Serialize};
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::config::{Config, Proposal};
+
+/// The largest page count of a 32-bit memory.
+const MAX_MEMORY_PAGES: u32 = 65536;
+
+/// Describes every option [Config::copy_from] copies. Options left out keep the defaults of
+/// [Config::create].
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigSpec {
+    /// The proposals to turn on; all others are turned off. `None` keeps the default proposals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proposals: Option<Vec<Proposal>>,
+    pub wasi: bool,
+    pub wasmedge_process: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_pages: Option<u32>,
+    pub statistics: StatisticsSpec,
+    pub aot: AotSpec,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatisticsSpec {
+    pub count_instructions: bool,
+    pub measure_cost: bool,
+    pub measure_time: bool,
+}
+
+/// The AOT compiler options, which only take effect with the `aot` feature.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AotSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimization_level: Option<AotOptimizationLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<AotOutputFormat>,
+    pub dump_ir: bool,
+    pub generic_binary: bool,
+    pub interruptible: bool,
+}
+
+impl AotSpec {
+    fn is_default(&self) -> bool {
+        *self == AotSpec::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AotOptimizationLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    Os,
+    Oz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AotOutputFormat {
+    Native,
+    Wasm,
+}
+
+fn spec_error(e: impl std::fmt::Display) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("invalid config spec: {}", e))
+}
+
+impl ConfigSpec {
+    /// Describes the options of `config`.
+    pub fn from_config(config: &Config) -> Self {
+        ConfigSpec {
+            proposals: Some(config.enabled_proposals()),
+            wasi: config.wasi_enabled(),
+            wasmedge_process: config.wasmedge_process_enabled(),
+            max_memory_pages: Some(config.get_max_memory_pages()),
+            statistics: StatisticsSpec {
+                count_instructions: config.is_instruction_counting(),
+                measure_cost: config.is_cost_measuring(),
+                measure_time: config.is_time_measuring(),
+            },
+            aot: AotSpec::from_config(config),
+        }
+    }
+
+    /// Checks the spec for options that cannot be combined, reporting all of them at once.
+    pub fn validate(&self) -> WasmEdgeResult<()> {
+        let mut problems = vec![];
+
+        if let Some(proposals) = &self.proposals {
+            let has = |proposal| proposals.contains(&proposal);
+            if has(Proposal::FunctionReferences) && !has(Proposal::ReferenceTypes) {
+                problems.push("function_references requires reference_types".to_string());
+            }
+            if has(Proposal::ReferenceTypes) && !has(Proposal::BulkMemoryOperations) {
+                problems.push("reference_types requires bulk_memory_operations".to_string());
+            }
+        }
+        if let Some(pages) = self.max_memory_pages {
+            if pages == 0 || pages > MAX_MEMORY_PAGES {
+                problems.push(format!(
+                    "max_memory_pages must be between 1 and {}, got {}",
+                    MAX_MEMORY_PAGES, pages
+                ));
+            }
+        }
+        if cfg!(not(feature = "aot")) && !self.aot.is_default() {
+            problems.push("aot options require the `aot` feature".to_string());
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(spec_error(problems.join("; "))),
+        }
+    }
+
+    /// Creates a [Config] from the spec.
+    ///
+    /// # Error
+    ///
+    /// If the spec is invalid, or if fail to create, then an error is returned.
+    pub fn to_config(&self) -> WasmEdgeResult<Config> {
+        self.validate()?;
+        let mut config = Config::create()?;
+
+        if let Some(proposals) = &self.proposals {
+            for proposal in Proposal::ALL {
+                config.proposal(proposal, proposals.contains(&proposal));
+            }
+        }
+        config.wasi(self.wasi);
+        config.wasmedge_process(self.wasmedge_process);
+        if let Some(pages) = self.max_memory_pages {
+            config.set_max_memory_pages(pages);
+        }
+        config.count_instructions(self.statistics.count_instructions);
+        config.measure_cost(self.statistics.measure_cost);
+        config.measure_time(self.statistics.measure_time);
+        self.aot.apply(&mut config);
+
+        Ok(config)
+    }
+
+    pub fn from_toml(toml: &str) -> WasmEdgeResult<Self> {
+        toml::from_str(toml).map_err(spec_error)
+    }
+
+    pub fn to_toml(&self) -> WasmEdgeResult<String> {
+        toml::to_string(self).map_err(spec_error)
+    }
+
+    pub fn from_json(json: &str) -> WasmEdgeResult<Self> {
+        serde_json::from_str(json).map_err(spec_error)
+    }
+
+    pub fn to_json(&self) -> WasmEdgeResult<String> {
+        serde_json::to_string_pretty(self).map_err(spec_error)
+    }
+}
+
+#[cfg(feature = "aot")]
+impl AotSpec {
+    fn from_config(config: &Config) -> Self {
+        use wasmedge_types::{CompilerOptimizationLevel, CompilerOutputFormat};
+
+        AotSpec {
+            optimization_level: Some(match config.get_aot_optimization_level() {
+                CompilerOptimizationLevel::O0 => AotOptimizationLevel::O0,
+                CompilerOptimizationLevel::O1 => AotOptimizationLevel::O1,
+                CompilerOptimizationLevel::O2 => AotOptimizationLevel::O2,
+                CompilerOptimizationLevel::O3 => AotOptimizationLevel::O3,
+                CompilerOptimizationLevel::Os => AotOptimizationLevel::Os,
+                CompilerOptimizationLevel::Oz => AotOptimizationLevel::Oz,
+            }),
+            output_format: Some(match config.get_aot_compiler_output_format() {
+                CompilerOutputFormat::Native => AotOutputFormat::Native,
+                CompilerOutputFormat::Wasm => AotOutputFormat::Wasm,
+            }),
+            dump_ir: config.dump_ir_enabled(),
+            generic_binary: config.generic_binary_enabled(),
+            interruptible: config.interruptible_enabled(),
+        }
+    }
+
+    fn apply(&self, config: &mut Config) {
+        use wasmedge_types::{CompilerOptimizationLevel, CompilerOutputFormat};
+
+        if let Some(level) = self.optimization_level {
+            config.set_aot_optimization_level(match level {
+                AotOptimizationLevel::O0 => CompilerOptimizationLevel::O0,
+                AotOptimizationLevel::O1 => CompilerOptimizationLevel::O1,
+                AotOptimizationLevel::O2 => CompilerOptimizationLevel::O2,
+                AotOptimizationLevel::O3 => CompilerOptimizationLevel::O3,
+                AotOptimizationLevel::Os => CompilerOptimizationLevel::Os,
+                AotOptimizationLevel::Oz => CompilerOptimizationLevel::Oz,
+            });
+        }
+        if let Some(format) = self.output_format {
+            config.set_aot_compiler_output_format(match format {
+                AotOutputFormat::Native => CompilerOutputFormat::Native,
+                AotOutputFormat::Wasm => CompilerOutputFormat::Wasm,
+            });
+        }
+        config.dump_ir(self.dump_ir);
+        config.generic_binary(self.generic_binary);
+        config.interruptible(self.interruptible);
+    }
+}
+
+#[cfg(not(feature = "aot"))]
+impl AotSpec {
+    fn from_config(_config: &Config) -> Self {
+        AotSpec::default()
+    }
+
+    fn apply(&self, _config: &mut Config) {}
+}
+
+impl From<&Config> for ConfigSpec {
+    fn from(config: &Config) -> Self {
+        ConfigSpec::from_config(config)
+    }
+}
+
+impl TryFrom<&ConfigSpec> for Config {
+    type Error = WasmEdgeError;
+
+    fn try_from(spec: &ConfigSpec) -> WasmEdgeResult<Self> {
+        spec.to_config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ConfigSpec {
+        ConfigSpec {
+            proposals: Some(vec![
+                Proposal::BulkMemoryOperations,
+                Proposal::MultiMemories,
+                Proposal::SIMD,
+            ]),
+            wasi: true,
+            max_memory_pages: Some(1024),
+            statistics: StatisticsSpec {
+                count_instructions: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let toml = spec().to_toml().unwrap();
+        assert_eq!(ConfigSpec::from_toml(&toml).unwrap(), spec());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = spec().to_json().unwrap();
+        assert_eq!(ConfigSpec::from_json(&json).unwrap(), spec());
+    }
+
+    #[test]
+    fn parses_partial_toml() {
+        let toml = r#"
+            proposals = ["bulk_memory_operations", "multi_memories", "simd"]
+            wasi = true
+            max_memory_pages = 1024
+
+            [statistics]
+            count_instructions = true
+        "#;
+        assert_eq!(ConfigSpec::from_toml(toml).unwrap(), spec());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(ConfigSpec::from_toml("wasi = true\nthreads = true").is_err());
+        assert!(ConfigSpec::from_json(r#"{"statistics": {"count_gas": true}}"#).is_err());
+    }
+
+    #[test]
+    fn reports_every_incompatible_option() {
+        let spec = ConfigSpec {
+            proposals: Some(vec![Proposal::FunctionReferences]),
+            max_memory_pages: Some(0),
+            ..Default::default()
+        };
+        let message = spec.validate().unwrap_err().to_string();
+        assert!(message.contains("function_references requires reference_types"));
+        assert!(message.contains("max_memory_pages must be between 1 and 65536, got 0"));
+        assert!(spec().validate().is_ok());
+    }
+}
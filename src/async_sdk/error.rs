@@ -0,0 +1,157 @@
+//! Defines the crate error type, which adds the failed operation, the export/import name and the
+//! raw WasmEdge result code to the underlying error.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use wasmedge_sys::ffi;
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::trap::TrapReport;
+use super::utils::{check, result_code};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The operation that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Parsing a wasm binary.
+    Load,
+    /// Validating a parsed module.
+    Validate,
+    /// Resolving the imports of a module against the registered import modules.
+    Link,
+    /// Instantiating a module.
+    Instantiate,
+    /// Calling a guest function.
+    Call,
+    /// Driving the asyncify state machine of an async module.
+    Asyncify,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Operation::Load => "load",
+            Operation::Validate => "validate",
+            Operation::Link => "link",
+            Operation::Instantiate => "instantiate",
+            Operation::Call => "call",
+            Operation::Asyncify => "asyncify",
+        };
+        f.write_str(op)
+    }
+}
+
+/// An error with the context it happened in.
+///
+/// The underlying error, usually a [WasmEdgeError], is available through [source](StdError::source).
+#[derive(Debug)]
+pub struct Error {
+    operation: Operation,
+    name: Option<String>,
+    code: Option<u32>,
+    trap: Option<TrapReport>,
+    source: Box<dyn StdError + Send + Sync>,
+}
+
+impl Error {
+    pub(crate) fn new(
+        operation: Operation,
+        source: impl Into<Box<dyn StdError + Send + Sync>>,
+    ) -> Self {
+        Error {
+            operation,
+            name: None,
+            code: None,
+            trap: None,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub(crate) fn with_code(mut self, code: u32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub(crate) fn with_trap(mut self, report: TrapReport) -> Self {
+        self.trap = Some(report);
+        self
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// Returns the name of the export or import the operation was about, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the WasmEdge result code, if the error came from the WasmEdge C API.
+    pub fn code(&self) -> Option<u32> {
+        self.code
+    }
+
+    /// Returns the guest call stack, if the error is a trap raised by a guest call.
+    pub fn trap(&self) -> Option<&TrapReport> {
+        self.trap.as_ref()
+    }
+
+    /// Returns the underlying error if it is a [WasmEdgeError].
+    pub fn wasmedge_error(&self) -> Option<&WasmEdgeError> {
+        self.source.downcast_ref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed", self.operation)?;
+        if let Some(name) = &self.name {
+            write!(f, " for `{}`", name)?;
+        }
+        if let Some(code) = self.code {
+            write!(f, " (code {:#04x})", code)?;
+        }
+        write!(f, ": {}", self.source)?;
+        if let Some(report) = &self.trap {
+            write!(f, "\n{}", report)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Flattens the context into [WasmEdgeError::Operation], so `?` keeps working in functions
+/// returning [WasmEdgeResult].
+impl From<Error> for WasmEdgeError {
+    fn from(err: Error) -> Self {
+        WasmEdgeError::Operation(err.to_string())
+    }
+}
+
+pub(crate) trait Context<T> {
+    fn context(self, operation: Operation) -> Result<T>;
+}
+
+impl<T> Context<T> for WasmEdgeResult<T> {
+    fn context(self, operation: Operation) -> Result<T> {
+        self.map_err(|e| Error::new(operation, e))
+    }
+}
+
+/// Like [check], keeping the result code.
+pub(crate) fn check_op(result: ffi::WasmEdge_Result, operation: Operation) -> Result<()> {
+    check(result).map_err(|e| Error::new(operation, e).with_code(result_code(result)))
+}
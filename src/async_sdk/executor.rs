@@ -5,8 +5,11 @@ use wasmedge_types::error::WasmEdgeError;
 use wasmedge_types::WasmEdgeResult;
 
 use super::ast_module::AstModule;
+use super::error::{self, check_op, Context, Operation};
 use super::instance::function::FuncRef;
+use super::interrupt::InterruptState;
 use super::module::{ImportModule, InnerInstance, Instance};
+use super::statistics::StatState;
 use super::types::WasmEdgeString;
 use super::utils::check;
 use super::{config::Config, types::WasmVal};
@@ -19,6 +22,9 @@ pub struct Executor {
     pub(crate) inner: InnerExecutor,
     pub(crate) inner_store: InnerStore,
     imports: HashMap<String, ImportModule>,
+    pub(crate) inner_stat: InnerStatistics,
+    pub(crate) stat_state: StatState,
+    pub(crate) interrupt: InterruptState,
 }
 impl Executor {
     pub fn create(config: &Option<Config>) -> WasmEdgeResult<Self> {
@@ -27,7 +33,8 @@ impl Executor {
                 Some(cfg) => cfg.inner.0,
                 None => std::ptr::null_mut(),
             };
-            let ctx = ffi::WasmEdge_ExecutorCreate(conf_ctx, std::ptr::null_mut());
+            let stat_ctx = InnerStatistics(ffi::WasmEdge_StatisticsCreate());
+            let ctx = ffi::WasmEdge_ExecutorCreate(conf_ctx, stat_ctx.0);
             let store_ctx = ffi::WasmEdge_StoreCreate();
 
             match ctx.is_null() {
@@ -36,6 +43,9 @@ impl Executor {
                     inner: InnerExecutor(ctx),
                     inner_store: InnerStore(store_ctx),
                     imports: HashMap::new(),
+                    inner_stat: stat_ctx,
+                    stat_state: StatState::default(),
+                    interrupt: InterruptState::default(),
                 }),
             }
         }
@@ -54,17 +64,26 @@ impl Executor {
         Ok(())
     }
 
-    pub fn register_active_module(&mut self, module: &AstModule) -> WasmEdgeResult<Instance> {
+    /// Returns the registered import module named `name`.
+    pub(crate) fn import_module(&self, name: &str) -> Option<&ImportModule> {
+        self.imports.get(name)
+    }
+
+    pub fn register_active_module(&mut self, module: &AstModule) -> error::Result<Instance> {
         let mut instance_ctx = std::ptr::null_mut();
         let name = WasmEdgeString::new("main");
         unsafe {
-            check(ffi::WasmEdge_ExecutorRegister(
-                self.inner.0,
-                &mut instance_ctx,
-                self.inner_store.0,
-                module.inner.0,
-                name.as_raw(),
-            ))?;
+            check_op(
+                ffi::WasmEdge_ExecutorRegister(
+                    self.inner.0,
+                    &mut instance_ctx,
+                    self.inner_store.0,
+                    module.inner.0,
+                    name.as_raw(),
+                ),
+                Operation::Instantiate,
+            )
+            .map_err(|e| e.with_name("main"))?;
         }
         Ok(Instance {
             inner: InnerInstance(instance_ctx),
@@ -75,23 +94,26 @@ impl Executor {
         &mut self,
         func: &FuncRef,
         params: &[WasmVal],
-    ) -> WasmEdgeResult<Vec<WasmVal>> {
+    ) -> error::Result<Vec<WasmVal>> {
         let raw_params = params.into_iter().map(|x| x.into()).collect::<Vec<_>>();
 
         // get the length of the function's returns
-        let returns_len = func.func_return_size()?;
+        let returns_len = func.func_return_size().context(Operation::Call)?;
 
         unsafe {
             let mut returns = Vec::with_capacity(returns_len);
 
-            check(ffi::WasmEdge_ExecutorInvoke(
-                self.inner.0,
-                func.inner.0,
-                raw_params.as_ptr(),
-                raw_params.len() as u32,
-                returns.as_mut_ptr(),
-                returns_len as u32,
-            ))?;
+            let result = self.timed(|executor| {
+                ffi::WasmEdge_ExecutorInvoke(
+                    executor.inner.0,
+                    func.inner.0,
+                    raw_params.as_ptr(),
+                    raw_params.len() as u32,
+                    returns.as_mut_ptr(),
+                    returns_len as u32,
+                )
+            });
+            check_op(result, Operation::Call)?;
             returns.set_len(returns_len);
             Ok(returns.into_iter().map(Into::into).collect::<Vec<_>>())
         }
@@ -121,3 +143,15 @@ impl Drop for InnerStore {
 }
 unsafe impl Send for InnerStore {}
 unsafe impl Sync for InnerStore {}
+
+#[derive(Debug)]
+pub(crate) struct InnerStatistics(pub(crate) *mut ffi::WasmEdge_StatisticsContext);
+impl Drop for InnerStatistics {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { ffi::WasmEdge_StatisticsDelete(self.0) }
+        }
+    }
+}
+unsafe impl Send for InnerStatistics {}
+unsafe impl Sync for InnerStatistics {}
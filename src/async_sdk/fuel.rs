@@ -0,0 +1,563 @@
+//! Defines fuel-based yielding, which lets a CPU-bound guest give other tasks a turn.
+//!
+//! With [Loader::set_yield_fuel](super::ast_module::Loader::set_yield_fuel),
+//! [load_async_module_from_bytes](super::ast_module::Loader::load_async_module_from_bytes) adds a
+//! fuel counter to the module and, at the start of every loop iteration, takes one unit of fuel from
+//! it. Once the fuel runs out, the guest calls the asyncified `wasmedge_async.fuel_yield` import,
+//! which suspends it until the next poll, and the fuel is refilled.
+//!
+//! [AsyncLinker](super::async_mod::AsyncLinker) registers the import itself when it instantiates such
+//! a module.
+//!
+//! The same loop checks call the [interrupt](super::interrupt) check of modules loaded with
+//! [Loader::set_interrupt_checks](super::ast_module::Loader::set_interrupt_checks).
+
+use std::convert::Infallible;
+
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, EntityType, GlobalSection, GlobalType, ImportSection,
+    IndirectNameMap, Instruction, Module, NameMap, NameSection, SectionId, TypeSection, ValType,
+};
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+use wasmparser::{KnownCustom, Name, Parser, Payload, TypeRef};
+
+use super::ast_module::AstModule;
+use super::async_mod::AsyncLinker;
+use super::error::{self, Error, Operation};
+use super::green_thread::green_yield_now;
+use super::module::ImportModule;
+
+/// The module of the import fuel checks call.
+pub const FUEL_MODULE: &str = "wasmedge_async";
+
+/// The name of the import fuel checks call.
+pub const FUEL_YIELD: &str = "fuel_yield";
+
+/// The import as listed in the `asyncify-imports` of the asyncify pass.
+pub(crate) const FUEL_YIELD_IMPORT: &str = "wasmedge_async.fuel_yield";
+
+/// The largest fuel the `i32` fuel counter of the guest holds.
+pub const MAX_YIELD_FUEL: u32 = i32::MAX as u32;
+
+/// A call to the import `module`.`name` made every `interval` loop iterations.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LoopCheck {
+    pub(crate) module: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) interval: u32,
+}
+
+/// The counts of the index spaces the injected import, type and global are appended to.
+#[derive(Debug, Default)]
+struct Counts {
+    types: u32,
+    imported_funcs: u32,
+    globals: u32,
+    has_code: bool,
+}
+
+impl Counts {
+    fn read(wasm: &[u8]) -> Result<Self, wasmparser::BinaryReaderError> {
+        let mut counts = Counts::default();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::TypeSection(reader) => {
+                    for rec_group in reader {
+                        counts.types += rec_group?.types().count() as u32;
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        match import?.ty {
+                            TypeRef::Func(_) => counts.imported_funcs += 1,
+                            TypeRef::Global(_) => counts.globals += 1,
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::GlobalSection(reader) => counts.globals += reader.count(),
+                Payload::CodeSectionStart { .. } => counts.has_code = true,
+                _ => {}
+            }
+        }
+        Ok(counts)
+    }
+}
+
+/// Re-encodes a module with a [LoopCheck] at the start of every loop.
+///
+/// The import is appended after the existing imports, so the functions defined by the module move
+/// up by one index, in the code and in the `name` section; the type and the global are appended at
+/// the end of their index spaces.
+struct LoopCheckInjector {
+    check: LoopCheck,
+    counts: Counts,
+    import_added: bool,
+    global_added: bool,
+}
+
+impl LoopCheckInjector {
+    fn check_func(&self) -> u32 {
+        self.counts.imported_funcs
+    }
+
+    fn counter_global(&self) -> u32 {
+        self.counts.globals
+    }
+
+    fn add_import(&mut self, imports: &mut ImportSection) {
+        imports.import(
+            self.check.module,
+            self.check.name,
+            EntityType::Function(self.counts.types),
+        );
+        self.import_added = true;
+    }
+
+    fn add_global(&mut self, globals: &mut GlobalSection) {
+        globals.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+                shared: false,
+            },
+            &ConstExpr::i32_const(self.check.interval as i32),
+        );
+        self.global_added = true;
+    }
+
+    /// Counts one loop iteration, and calls the import and restarts the count once it runs out.
+    fn loop_check(&self) -> [Instruction<'static>; 11] {
+        let counter = self.counter_global();
+        [
+            Instruction::GlobalGet(counter),
+            Instruction::I32Const(1),
+            Instruction::I32Sub,
+            Instruction::GlobalSet(counter),
+            Instruction::GlobalGet(counter),
+            Instruction::I32Eqz,
+            Instruction::If(BlockType::Empty),
+            Instruction::Call(self.check_func()),
+            Instruction::I32Const(self.check.interval as i32),
+            Instruction::GlobalSet(counter),
+            Instruction::End,
+        ]
+    }
+}
+
+/// Copies `map`, moving its indices along with the functions if they are function indices.
+fn name_map<R: Reencode>(
+    reencoder: &mut R,
+    map: wasmparser::NameMap<'_>,
+    funcs: bool,
+) -> Result<NameMap, wasmparser::BinaryReaderError> {
+    let mut names = NameMap::new();
+    for naming in map {
+        let naming = naming?;
+        let index = match funcs {
+            true => reencoder.function_index(naming.index),
+            false => naming.index,
+        };
+        names.append(index, naming.name);
+    }
+    Ok(names)
+}
+
+/// Copies `map`, moving its outer indices along with the functions if they are function indices.
+fn indirect_name_map<R: Reencode>(
+    reencoder: &mut R,
+    map: wasmparser::IndirectNameMap<'_>,
+    funcs: bool,
+) -> Result<IndirectNameMap, wasmparser::BinaryReaderError> {
+    let mut names = IndirectNameMap::new();
+    for naming in map {
+        let naming = naming?;
+        let index = match funcs {
+            true => reencoder.function_index(naming.index),
+            false => naming.index,
+        };
+        names.append(index, &name_map(reencoder, naming.names, false)?);
+    }
+    Ok(names)
+}
+
+/// Copies a custom section, moving the function indices of the `name` section along with the
+/// functions, as [Reencode::function_index] moves them.
+pub(crate) fn parse_custom_section<R: Reencode>(
+    reencoder: &mut R,
+    module: &mut Module,
+    section: wasmparser::CustomSectionReader<'_>,
+) -> Result<(), reencode::Error<R::Error>> {
+    let reader = match section.as_known() {
+        KnownCustom::Name(reader) => reader,
+        _ => return reencode::utils::parse_custom_section(reencoder, module, section),
+    };
+    let mut names = NameSection::new();
+    for name in reader {
+        match name? {
+            Name::Module { name, .. } => names.module(name),
+            Name::Function(map) => names.functions(&name_map(reencoder, map, true)?),
+            Name::Local(map) => names.locals(&indirect_name_map(reencoder, map, true)?),
+            Name::Label(map) => names.labels(&indirect_name_map(reencoder, map, true)?),
+            Name::Type(map) => names.types(&name_map(reencoder, map, false)?),
+            Name::Table(map) => names.tables(&name_map(reencoder, map, false)?),
+            Name::Memory(map) => names.memories(&name_map(reencoder, map, false)?),
+            Name::Global(map) => names.globals(&name_map(reencoder, map, false)?),
+            Name::Element(map) => names.elements(&name_map(reencoder, map, false)?),
+            Name::Data(map) => names.data(&name_map(reencoder, map, false)?),
+            Name::Tag(map) => names.tags(&name_map(reencoder, map, false)?),
+            Name::Field(map) => names.fields(&indirect_name_map(reencoder, map, false)?),
+            Name::Unknown { ty, data, .. } => names.raw(ty, data),
+        }
+    }
+    module.section(&names);
+    Ok(())
+}
+
+/// Returns the position of a known section in the order sections must appear in.
+fn section_rank(id: SectionId) -> Option<u8> {
+    Some(match id {
+        SectionId::Type => 1,
+        SectionId::Import => 2,
+        SectionId::Function => 3,
+        SectionId::Table => 4,
+        SectionId::Memory => 5,
+        SectionId::Tag => 6,
+        SectionId::Global => 7,
+        SectionId::Export => 8,
+        SectionId::Start => 9,
+        SectionId::Element => 10,
+        SectionId::DataCount => 11,
+        SectionId::Code => 12,
+        SectionId::Data => 13,
+        _ => return None,
+    })
+}
+
+/// Returns `true` if a section about to be written, `None` at the end, must come after `section`.
+pub(crate) fn comes_after(before: Option<SectionId>, section: SectionId) -> bool {
+    match before {
+        None => true,
+        Some(id) => section_rank(id) > section_rank(section),
+    }
+}
+
+impl Reencode for LoopCheckInjector {
+    type Error = Infallible;
+
+    fn function_index(&mut self, func: u32) -> u32 {
+        match func < self.counts.imported_funcs {
+            true => func,
+            false => func + 1,
+        }
+    }
+
+    fn parse_type_section(
+        &mut self,
+        types: &mut TypeSection,
+        section: wasmparser::TypeSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_type_section(self, types, section)?;
+        types.ty().function(vec![], vec![]);
+        Ok(())
+    }
+
+    fn parse_import_section(
+        &mut self,
+        imports: &mut ImportSection,
+        section: wasmparser::ImportSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_import_section(self, imports, section)?;
+        self.add_import(imports);
+        Ok(())
+    }
+
+    fn parse_global_section(
+        &mut self,
+        globals: &mut GlobalSection,
+        section: wasmparser::GlobalSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_global_section(self, globals, section)?;
+        self.add_global(globals);
+        Ok(())
+    }
+
+    fn parse_function_body(
+        &mut self,
+        code: &mut CodeSection,
+        func: wasmparser::FunctionBody<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        let mut f = self.new_function_with_parsed_locals(&func)?;
+        let mut reader = func.get_operators_reader()?;
+        while !reader.eof() {
+            let op = reader.read()?;
+            let is_loop = matches!(op, wasmparser::Operator::Loop { .. });
+            f.instruction(&self.instruction(op)?);
+            if is_loop {
+                for instruction in self.loop_check() {
+                    f.instruction(&instruction);
+                }
+            }
+        }
+        code.function(&f);
+        Ok(())
+    }
+
+    /// Moves the function indices of the `name` section along with the functions.
+    fn parse_custom_section(
+        &mut self,
+        module: &mut Module,
+        section: wasmparser::CustomSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        parse_custom_section(self, module, section)
+    }
+
+    /// Adds the import and global sections the module does not have.
+    fn intersperse_section_hook(
+        &mut self,
+        module: &mut Module,
+        _after: Option<SectionId>,
+        before: Option<SectionId>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        if !self.import_added && comes_after(before, SectionId::Import) {
+            let mut imports = ImportSection::new();
+            self.add_import(&mut imports);
+            module.section(&imports);
+        }
+        if !self.global_added && comes_after(before, SectionId::Global) {
+            let mut globals = GlobalSection::new();
+            self.add_global(&mut globals);
+            module.section(&globals);
+        }
+        Ok(())
+    }
+}
+
+fn inject_error(check: LoopCheck, e: impl std::fmt::Display) -> Error {
+    Error::new(
+        Operation::Load,
+        WasmEdgeError::Operation(format!(
+            "cannot inject {}.{} checks: {}",
+            check.module, check.name, e
+        )),
+    )
+}
+
+/// Adds `check` to every loop of `wasm`.
+pub(crate) fn inject_loop_checks(wasm: &[u8], check: LoopCheck) -> error::Result<Vec<u8>> {
+    let counts = Counts::read(wasm).map_err(|e| inject_error(check, e))?;
+    if !counts.has_code {
+        return Ok(wasm.to_vec());
+    }
+
+    let mut injector = LoopCheckInjector {
+        check: LoopCheck {
+            interval: check.interval.max(1),
+            ..check
+        },
+        counts,
+        import_added: false,
+        global_added: false,
+    };
+    let mut module = Module::new();
+    injector
+        .parse_core_module(&mut module, Parser::new(0), wasm)
+        .map_err(|e| inject_error(check, e))?;
+    Ok(module.finish())
+}
+
+/// Adds a fuel check to every loop of `wasm`, yielding every `fuel` loop iterations.
+pub(crate) fn inject_fuel_checks(wasm: &[u8], fuel: u32) -> error::Result<Vec<u8>> {
+    inject_loop_checks(
+        wasm,
+        LoopCheck {
+            module: FUEL_MODULE,
+            name: FUEL_YIELD,
+            interval: fuel,
+        },
+    )
+}
+
+impl AsyncLinker {
+    /// Registers the import fuel checks call, if `module` imports it and it is not registered yet.
+    pub(crate) fn register_fuel_yield(&mut self, module: &AstModule) -> WasmEdgeResult<()> {
+        let imported = module
+            .imports()
+            .iter()
+            .any(|import| import.module == FUEL_MODULE && import.name == FUEL_YIELD);
+        if !imported
+            || self
+                .real_linker
+                .executor
+                .import_module(FUEL_MODULE)
+                .is_some()
+        {
+            return Ok(());
+        }
+
+        let mut import_obj = ImportModule::create(FUEL_MODULE)?;
+        import_obj.add_async_func(FUEL_YIELD, self, (vec![], vec![]), green_yield_now, 0)?;
+        self.real_linker.register_import(import_obj)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmparser::{Operator, Validator, WasmFeatures};
+
+    const LOOP: &str = r#"(module
+        (import "env" "log" (func $log (param i32)))
+        (func $spin (export "spin") (param $n i32)
+            (loop $again
+                (call $log (local.get $n))
+                (call $dec)
+                (br_if $again (local.tee $n (i32.sub (local.get $n) (i32.const 1))))))
+        (func $dec))"#;
+
+    fn inject(wat: &str, fuel: u32) -> Vec<u8> {
+        let wasm = inject_fuel_checks(&wat::parse_str(wat).unwrap(), fuel).unwrap();
+        Validator::new_with_features(WasmFeatures::default())
+            .validate_all(&wasm)
+            .unwrap();
+        wasm
+    }
+
+    fn imports(wasm: &[u8]) -> Vec<(String, String)> {
+        let mut imports = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::ImportSection(reader) = payload.unwrap() {
+                for import in reader {
+                    let import = import.unwrap();
+                    imports.push((import.module.to_string(), import.name.to_string()));
+                }
+            }
+        }
+        imports
+    }
+
+    fn func_names(wasm: &[u8]) -> Vec<(u32, String)> {
+        let mut names = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::CustomSection(section) = payload.unwrap() {
+                if let KnownCustom::Name(reader) = section.as_known() {
+                    for name in reader {
+                        if let Name::Function(map) = name.unwrap() {
+                            for naming in map {
+                                let naming = naming.unwrap();
+                                names.push((naming.index, naming.name.to_string()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// Returns the operators of the body of every defined function.
+    fn bodies(wasm: &[u8]) -> Vec<Vec<String>> {
+        let mut bodies = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                let ops = body
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter()
+                    .map(|op| format!("{:?}", op.unwrap()))
+                    .collect();
+                bodies.push(ops);
+            }
+        }
+        bodies
+    }
+
+    #[test]
+    fn fuel_import_is_appended() {
+        let wasm = inject(LOOP, 100);
+        assert_eq!(
+            imports(&wasm),
+            [
+                ("env".to_string(), "log".to_string()),
+                (FUEL_MODULE.to_string(), FUEL_YIELD.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn fuel_check_follows_every_loop() {
+        let wasm = inject(LOOP, 100);
+        let spin = &bodies(&wasm)[0];
+        let at = spin.iter().position(|op| op.starts_with("Loop")).unwrap();
+        assert_eq!(
+            spin[at + 1],
+            format!("{:?}", Operator::GlobalGet { global_index: 0 })
+        );
+        assert_eq!(
+            spin[at + 2],
+            format!("{:?}", Operator::I32Const { value: 1 })
+        );
+        // the yield call comes before the calls of the loop body
+        assert_eq!(
+            spin[at + 8],
+            format!("{:?}", Operator::Call { function_index: 1 })
+        );
+        assert_eq!(
+            spin[at + 9],
+            format!("{:?}", Operator::I32Const { value: 100 })
+        );
+    }
+
+    #[test]
+    fn defined_functions_move_up() {
+        let wasm = inject(LOOP, 100);
+        let spin = &bodies(&wasm)[0];
+        let calls = spin
+            .iter()
+            .filter(|op| op.starts_with("Call "))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                format!("{:?}", Operator::Call { function_index: 1 }),
+                format!("{:?}", Operator::Call { function_index: 0 }),
+                format!("{:?}", Operator::Call { function_index: 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn function_names_move_up() {
+        let wasm = inject(LOOP, 100);
+        assert_eq!(
+            func_names(&wasm),
+            [
+                (0, "log".to_string()),
+                (2, "spin".to_string()),
+                (3, "dec".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_sections_are_added() {
+        let wasm = inject("(module (func (loop)))", 5);
+        assert_eq!(
+            imports(&wasm),
+            [(FUEL_MODULE.to_string(), FUEL_YIELD.to_string())]
+        );
+        assert_eq!(
+            bodies(&wasm)[0][1],
+            format!("{:?}", Operator::GlobalGet { global_index: 0 })
+        );
+    }
+
+    #[test]
+    fn module_without_code_is_unchanged() {
+        let wasm = wat::parse_str("(module (memory 1))").unwrap();
+        assert_eq!(inject_fuel_checks(&wasm, 5).unwrap(), wasm);
+    }
+}
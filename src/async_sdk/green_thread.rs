@@ -0,0 +1,364 @@
+//! Defines guest-spawned tasks (green threads) scheduled cooperatively by [AsyncLinker](super::async_mod::AsyncLinker).
+//!
+//! A guest starts a task with the `spawn(func_ref, arg)` import, where `func_ref` is an index into the
+//! exported `__indirect_function_table` of a `(i32) -> i32` function. Each task owns its own asyncify stack:
+//! whenever the outer call is unwound, the linker swaps the asyncify memory and the pending host futures of
+//! every unfinished task in, resumes it until it suspends or returns, and swaps the outer call back.
+//!
+//! `join` and `yield_now` suspend the caller, so they must be listed in the `async_fn_names` passed to
+//! [load_async_module_from_bytes](super::ast_module::Loader::load_async_module_from_bytes).
+//! Tasks that are still unfinished when the outer call returns are dropped.
+//!
+//! Tasks are not driven by the executor on their own: they only get a turn when the outer call
+//! suspends, i.e. when it awaits a host future, `join`s or calls `yield_now`. A guest that spawns
+//! tasks and then computes without suspending runs them only once it does.
+
+use std::collections::{BTreeMap, LinkedList};
+use std::pin::Pin;
+use std::task::{Poll, Waker};
+
+use wasmedge_types::error::{InstanceError, WasmEdgeError};
+use wasmedge_types::{ValType, WasmEdgeResult};
+
+use super::async_mod::{AsyncImportModuleBuilder, AsyncLinker, ResultFuture};
+use super::error::{self, Context, Operation};
+use super::instance::function::FuncRef;
+use super::instance::memory::Memory;
+use super::module::AsInstance;
+use super::types::WasmVal;
+
+/// The name of the secondary memory the asyncify pass keeps unwound call stacks in.
+pub(crate) const ASYNCIFY_MEMORY: &str = "asyncify_memory";
+
+/// The size of the asyncify data header at the start of the asyncify memory: the current and the end
+/// pointer of the unwound stack, which follows it.
+const ASYNCIFY_DATA_HEADER: u32 = 8;
+
+/// The table guest function pointers index into.
+pub(crate) const INDIRECT_FUNCTION_TABLE: &str = "__indirect_function_table";
+
+pub(crate) type TaskFutures = LinkedList<Pin<ResultFuture<'static>>>;
+
+pub(crate) struct GreenTask {
+    func: FuncRef,
+    arg: i32,
+    started: bool,
+    stack: Vec<u8>,
+    futures: TaskFutures,
+    result: Option<WasmEdgeResult<Vec<WasmVal>>>,
+    /// The waker of the `join` waiting on the task, woken once it finishes.
+    joiner: Option<Waker>,
+}
+
+impl GreenTask {
+    fn finish(&mut self, result: WasmEdgeResult<Vec<WasmVal>>) {
+        self.result = Some(result);
+        if let Some(joiner) = self.joiner.take() {
+            joiner.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TaskTable {
+    next_handle: i32,
+    tasks: BTreeMap<i32, GreenTask>,
+}
+
+impl TaskTable {
+    fn spawn(&mut self, func: FuncRef, arg: i32) -> WasmEdgeResult<i32> {
+        let handle = self.next_handle.checked_add(1).ok_or_else(|| {
+            WasmEdgeError::Operation("no task handles left to spawn with".to_string())
+        })?;
+        self.next_handle = handle;
+        self.tasks.insert(
+            handle,
+            GreenTask {
+                func,
+                arg,
+                started: false,
+                stack: vec![],
+                futures: LinkedList::new(),
+                result: None,
+                joiner: None,
+            },
+        );
+        Ok(handle)
+    }
+
+    fn runnable(&self) -> Vec<i32> {
+        self.tasks
+            .iter()
+            .filter(|(_, task)| task.result.is_none())
+            .map(|(handle, _)| *handle)
+            .collect()
+    }
+
+    /// Takes the result of a finished task, releasing its handle.
+    ///
+    /// If the task is unfinished, `joiner` is woken once it finishes.
+    fn take_result(&mut self, handle: i32, joiner: &Waker) -> WasmEdgeResult<Option<Vec<WasmVal>>> {
+        match self.tasks.get_mut(&handle) {
+            None => Err(WasmEdgeError::Operation(format!(
+                "unknown task handle: {}",
+                handle
+            ))),
+            Some(task) if task.result.is_none() => {
+                task.joiner = Some(joiner.clone());
+                Ok(None)
+            }
+            Some(_) => {
+                let task = self.tasks.remove(&handle).unwrap();
+                task.result.unwrap().map(Some)
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.tasks.clear();
+    }
+}
+
+impl AsyncLinker {
+    /// Returns the function stored at `idx` in the exported indirect function table.
+    pub(crate) fn get_table_func(&self, idx: u32) -> WasmEdgeResult<FuncRef> {
+        self.get_table(INDIRECT_FUNCTION_TABLE)?.get_func(idx)
+    }
+
+    fn asyncify_memory(&self) -> error::Result<Memory> {
+        let inst = self.real_linker.inst.as_ref().ok_or_else(|| {
+            WasmEdgeError::Instance(InstanceError::NotFoundMem(ASYNCIFY_MEMORY.to_string()))
+        });
+        inst.and_then(|inst| inst.get_memory(ASYNCIFY_MEMORY))
+            .context(Operation::Asyncify)
+            .map_err(|e| e.with_name(ASYNCIFY_MEMORY))
+    }
+
+    /// Copies the used part of the asyncify memory: the asyncify data header and the stack up to the
+    /// current stack pointer it holds.
+    ///
+    /// If the header does not point into the memory, the whole memory is copied.
+    pub(crate) fn asyncify_stack(&self) -> error::Result<Vec<u8>> {
+        let mem = self.asyncify_memory()?;
+        let size = mem.size() * 65536;
+        let header = mem
+            .get_data(0, ASYNCIFY_DATA_HEADER.min(size))
+            .context(Operation::Asyncify)?;
+        let used = match header[..] {
+            [a, b, c, d, ..] => u32::from_le_bytes([a, b, c, d]),
+            _ => size,
+        };
+        let len = match used {
+            used if used > size => size,
+            used => used.max(ASYNCIFY_DATA_HEADER.min(size)),
+        };
+        mem.get_data(0, len).context(Operation::Asyncify)
+    }
+
+    pub(crate) fn set_asyncify_stack(&mut self, stack: &[u8]) -> error::Result<()> {
+        let mut mem = self.asyncify_memory()?;
+        mem.set_data(stack, 0).context(Operation::Asyncify)
+    }
+
+    /// Runs every unfinished task until it suspends or returns.
+    ///
+    /// Must only be called while the outer call is unwound. A `join` waiting on a task that finishes
+    /// is woken. The outer call's stack and pending host futures are put back even if a task fails
+    /// to switch.
+    pub(crate) fn run_green_tasks(&mut self) -> error::Result<()> {
+        let runnable = self.tasks.runnable();
+        if runnable.is_empty() {
+            return Ok(());
+        }
+
+        let outer_stack = self.asyncify_stack()?;
+        let mut outer_futures = std::mem::take(self.task_futures());
+
+        let mut r = Ok(());
+        for handle in runnable {
+            let mut task = match self.tasks.tasks.remove(&handle) {
+                Some(task) => task,
+                None => continue,
+            };
+
+            std::mem::swap(self.task_futures(), &mut task.futures);
+            let step = self.run_green_task(&mut task);
+            std::mem::swap(self.task_futures(), &mut task.futures);
+            if task.result.is_some() {
+                task.futures.clear();
+            }
+            self.tasks.tasks.insert(handle, task);
+
+            if let Err(e) = step {
+                r = Err(e);
+                break;
+            }
+        }
+
+        std::mem::swap(self.task_futures(), &mut outer_futures);
+        // put the outer call back into the unwound state so the next poll rewinds it
+        let restored = self
+            .set_asyncify_stack(&outer_stack)
+            .and_then(|_| self.asyncify_interrupt());
+        r.and(restored)
+    }
+
+    /// Runs `task` until it suspends or returns.
+    fn run_green_task(&mut self, task: &mut GreenTask) -> error::Result<()> {
+        let r = if task.started {
+            self.set_asyncify_stack(&task.stack)
+                .and_then(|_| self.asyncify_replay())
+        } else {
+            task.started = true;
+            self.asyncify_normal()
+        }
+        .and_then(|_| {
+            let func = task.func.clone();
+            self.call_depth += 1;
+            let r = self
+                .real_linker
+                .executor
+                .run_func_ref(&func, &[WasmVal::I32(task.arg)]);
+            self.call_depth -= 1;
+            r
+        });
+
+        match r {
+            Ok(v) if self.asyncify_done() => {
+                task.finish(Ok(v));
+                Ok(())
+            }
+            Ok(_) => {
+                task.stack = self.asyncify_stack()?;
+                Ok(())
+            }
+            Err(e) => {
+                task.finish(Err(e.into()));
+                self.asyncify_normal()
+            }
+        }
+    }
+}
+
+fn green_spawn(linker: &mut AsyncLinker, args: Vec<WasmVal>) -> ResultFuture {
+    Box::new(async move {
+        match args[..] {
+            [WasmVal::I32(func_idx), WasmVal::I32(arg)] => {
+                let func = linker.get_table_func(func_idx as u32)?;
+                let handle = linker.tasks.spawn(func, arg)?;
+                Ok(vec![WasmVal::I32(handle)])
+            }
+            _ => Err(WasmEdgeError::Operation(
+                "spawn expects (i32, i32)".to_string(),
+            )),
+        }
+    })
+}
+
+fn green_join(linker: &mut AsyncLinker, args: Vec<WasmVal>) -> ResultFuture {
+    let handle = match args.first() {
+        Some(WasmVal::I32(handle)) => *handle,
+        _ => -1,
+    };
+    Box::new(std::future::poll_fn(move |cx| {
+        match linker.tasks.take_result(handle, cx.waker()) {
+            Ok(Some(v)) => Poll::Ready(Ok(v)),
+            // woken by the scheduler once the task finishes
+            Ok(None) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }))
+}
+
+pub(crate) fn green_yield_now(_linker: &mut AsyncLinker, _args: Vec<WasmVal>) -> ResultFuture {
+    let mut yielded = false;
+    Box::new(std::future::poll_fn(move |cx| {
+        if yielded {
+            Poll::Ready(Ok(vec![]))
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }))
+}
+
+impl AsyncImportModuleBuilder<'_> {
+    /// Adds the `spawn`, `join` and `yield_now` imports to this import module.
+    ///
+    /// * `spawn(func_ref: i32, arg: i32) -> i32` starts a task and returns its handle.
+    ///
+    /// * `join(handle: i32) -> i32` suspends until the task returns and yields its result.
+    ///
+    /// * `yield_now()` suspends the caller once so that other tasks can run.
+    pub fn add_green_thread_funcs(&mut self) -> WasmEdgeResult<()> {
+        self.add_func(
+            "spawn",
+            (vec![ValType::I32, ValType::I32], vec![ValType::I32]),
+            green_spawn,
+            0,
+        )?;
+        self.add_func(
+            "join",
+            (vec![ValType::I32], vec![ValType::I32]),
+            green_join,
+            0,
+        )?;
+        self.add_func("yield_now", (vec![], vec![]), green_yield_now, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::super::instance::function::InnerFunc;
+    use super::*;
+
+    fn func() -> FuncRef {
+        FuncRef {
+            inner: InnerFunc(std::ptr::null()),
+        }
+    }
+
+    #[test]
+    fn join_is_woken_when_the_task_finishes() {
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = {
+            let wakes = wakes.clone();
+            waker_fn::waker_fn(move || {
+                wakes.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        let mut tasks = TaskTable::default();
+        let handle = tasks.spawn(func(), 7).unwrap();
+        assert!(matches!(tasks.take_result(handle, &waker), Ok(None)));
+        assert_eq!(wakes.load(Ordering::SeqCst), 0);
+
+        let task = tasks.tasks.get_mut(&handle).unwrap();
+        task.finish(Ok(vec![WasmVal::I32(1)]));
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+        assert!(tasks.runnable().is_empty());
+
+        let result = tasks.take_result(handle, &waker).unwrap().unwrap();
+        assert!(matches!(result[..], [WasmVal::I32(1)]));
+        assert!(tasks.is_empty());
+        assert!(tasks.take_result(handle, &waker).is_err());
+    }
+
+    #[test]
+    fn spawn_runs_out_of_handles() {
+        let mut tasks = TaskTable {
+            next_handle: i32::MAX - 1,
+            ..TaskTable::default()
+        };
+        assert_eq!(tasks.spawn(func(), 0).unwrap(), i32::MAX);
+        assert!(tasks.spawn(func(), 0).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Defines allocation of buffers in guest memory through the guest's own allocator.
+//!
+//! The allocator is found by convention on the active instance, and the same one is used to allocate
+//! and to free: the exported `malloc(size) -> ptr` / `free(ptr)` pair is preferred, otherwise an
+//! exported `cabi_realloc(old_ptr, old_size, align, new_size) -> ptr` allocates. Buffers allocated by
+//! `cabi_realloc` cannot be freed by the host; they are meant to be handed over to the guest.
+//!
+//! An empty buffer is never allocated: it is the empty slice at offset 0, and freeing it does nothing.
+
+use wasmedge_types::error::{InstanceError, WasmEdgeError};
+use wasmedge_types::WasmEdgeResult;
+
+use super::async_mod::AsyncLinker;
+use super::module::{AsInstance, Instance};
+use super::types::WasmVal;
+use super::wasm_ptr::WasmSlice;
+use super::Linker;
+
+pub const REALLOC_EXPORT: &str = "cabi_realloc";
+pub const MALLOC_EXPORT: &str = "malloc";
+pub const FREE_EXPORT: &str = "free";
+
+fn has_func(inst: &Instance, name: &str) -> bool {
+    inst.get_func(name).is_ok()
+}
+
+/// The guest allocator of an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Allocator {
+    MallocFree,
+    CabiRealloc,
+}
+
+impl Allocator {
+    fn find(inst: Option<&Instance>) -> WasmEdgeResult<Self> {
+        match inst {
+            Some(inst) if has_func(inst, MALLOC_EXPORT) && has_func(inst, FREE_EXPORT) => {
+                Ok(Allocator::MallocFree)
+            }
+            Some(inst) if has_func(inst, REALLOC_EXPORT) => Ok(Allocator::CabiRealloc),
+            _ => Err(WasmEdgeError::Instance(InstanceError::NotFoundFunc(
+                MALLOC_EXPORT.to_string(),
+            ))),
+        }
+    }
+
+    /// Returns the export and the arguments allocating `size` bytes.
+    fn alloc_call(self, size: u32) -> WasmEdgeResult<(&'static str, Vec<WasmVal>)> {
+        let size = guest_i32(size)?;
+        Ok(match self {
+            Allocator::MallocFree => (MALLOC_EXPORT, vec![WasmVal::I32(size)]),
+            Allocator::CabiRealloc => (
+                REALLOC_EXPORT,
+                vec![
+                    WasmVal::I32(0),
+                    WasmVal::I32(0),
+                    WasmVal::I32(1),
+                    WasmVal::I32(size),
+                ],
+            ),
+        })
+    }
+
+    /// Returns the export and the arguments releasing `slice`.
+    fn free_call(self, slice: &WasmSlice<u8>) -> WasmEdgeResult<(&'static str, Vec<WasmVal>)> {
+        match self {
+            Allocator::MallocFree => {
+                Ok((FREE_EXPORT, vec![WasmVal::I32(guest_i32(slice.offset())?)]))
+            }
+            Allocator::CabiRealloc => Err(WasmEdgeError::Instance(InstanceError::NotFoundFunc(
+                FREE_EXPORT.to_string(),
+            ))),
+        }
+    }
+}
+
+/// Converts `len`, a host buffer length, to the length of a guest buffer.
+fn guest_len(len: usize) -> WasmEdgeResult<u32> {
+    u32::try_from(len).map_err(|_| too_large(len))
+}
+
+/// Converts `val` to an `i32` argument of the guest allocator.
+fn guest_i32(val: u32) -> WasmEdgeResult<i32> {
+    i32::try_from(val).map_err(|_| too_large(val as usize))
+}
+
+fn too_large(len: usize) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("{} does not fit in the guest address space", len))
+}
+
+fn returned_ptr(name: &str, returns: Vec<WasmVal>) -> WasmEdgeResult<u32> {
+    match returns.first() {
+        Some(WasmVal::I32(0)) => Err(WasmEdgeError::Operation(format!(
+            "{} failed to allocate",
+            name
+        ))),
+        Some(WasmVal::I32(ptr)) => Ok(*ptr as u32),
+        _ => Err(WasmEdgeError::Operation(format!(
+            "{} did not return a pointer",
+            name
+        ))),
+    }
+}
+
+impl Linker {
+    /// Allocates a buffer with the guest's allocator and copies `bytes` into it.
+    ///
+    /// The guest owns the returned buffer; it can be handed back to the guest, or released with
+    /// [free_in_guest](Linker::free_in_guest).
+    ///
+    /// # Error
+    ///
+    /// If `bytes` is larger than the guest can address, or the allocator fails, then an error is
+    /// returned.
+    pub fn alloc_in_guest(&mut self, bytes: &[u8]) -> WasmEdgeResult<WasmSlice<u8>> {
+        let len = guest_len(bytes.len())?;
+        if len == 0 {
+            return Ok(WasmSlice::new(0, 0));
+        }
+        let (name, args) = Allocator::find(self.inst.as_ref())?.alloc_call(len)?;
+        let ptr = returned_ptr(name, self.run(name, &args)?)?;
+        self.memory()?.write_bytes(ptr, bytes)?;
+        Ok(WasmSlice::new(ptr, len))
+    }
+
+    /// Releases a buffer returned by [alloc_in_guest](Linker::alloc_in_guest).
+    ///
+    /// # Error
+    ///
+    /// If the guest only exports `cabi_realloc`, which cannot free, then an error is returned.
+    pub fn free_in_guest(&mut self, slice: WasmSlice<u8>) -> WasmEdgeResult<()> {
+        if slice.is_empty() {
+            return Ok(());
+        }
+        let (name, args) = Allocator::find(self.inst.as_ref())?.free_call(&slice)?;
+        self.run(name, &args)?;
+        Ok(())
+    }
+}
+
+impl AsyncLinker {
+    /// Allocates a buffer with the guest's allocator and copies `bytes` into it.
+    ///
+    /// See [Linker::alloc_in_guest].
+    pub fn alloc_in_guest(&mut self, bytes: &[u8]) -> WasmEdgeResult<WasmSlice<u8>> {
+        let len = guest_len(bytes.len())?;
+        if len == 0 {
+            return Ok(WasmSlice::new(0, 0));
+        }
+        let (name, args) = Allocator::find(self.real_linker.inst.as_ref())?.alloc_call(len)?;
+        let ptr = returned_ptr(name, self.call_sync(name, &args)?)?;
+        self.memory()?.write_bytes(ptr, bytes)?;
+        Ok(WasmSlice::new(ptr, len))
+    }
+
+    /// Releases a buffer returned by [alloc_in_guest](AsyncLinker::alloc_in_guest).
+    pub fn free_in_guest(&mut self, slice: WasmSlice<u8>) -> WasmEdgeResult<()> {
+        if slice.is_empty() {
+            return Ok(());
+        }
+        let (name, args) = Allocator::find(self.real_linker.inst.as_ref())?.free_call(&slice)?;
+        self.call_sync(name, &args)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::AsLinker;
+    use super::*;
+
+    /// A bump allocator that traps on empty allocations and counts frees.
+    const BUMP: &str = r#"(module
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 16))
+        (global $frees (export "frees") (mut i32) (i32.const 0))
+        (func (export "malloc") (param $size i32) (result i32)
+            (global.get $next)
+            (if (i32.eqz (local.get $size)) (then unreachable))
+            (global.set $next (i32.add (global.get $next) (local.get $size))))
+        (func (export "free") (param i32)
+            (global.set $frees (i32.add (global.get $frees) (i32.const 1)))))"#;
+
+    fn linker() -> Box<Linker> {
+        let module = Loader::create(&None).unwrap().load_from_wat(BUMP).unwrap();
+        let mut linker = Linker::new(&None).unwrap();
+        linker.active_module(&module).unwrap();
+        linker
+    }
+
+    fn frees(linker: &Linker) -> WasmVal {
+        linker.get_global("frees").unwrap().get_value()
+    }
+
+    #[test]
+    fn alloc_copies_into_the_guest() {
+        let mut linker = linker();
+        let slice = linker.alloc_in_guest(b"hello").unwrap();
+        assert_eq!((slice.offset(), slice.len()), (16, 5));
+        let mem = linker.memory().unwrap();
+        assert_eq!(slice.read_bytes(&mem).unwrap(), b"hello");
+
+        linker.free_in_guest(slice).unwrap();
+        assert!(matches!(frees(&linker), WasmVal::I32(1)));
+    }
+
+    #[test]
+    fn empty_alloc_skips_the_allocator() {
+        let mut linker = linker();
+        let slice = linker.alloc_in_guest(b"").unwrap();
+        assert!(slice.is_empty());
+        linker.free_in_guest(slice).unwrap();
+        assert!(matches!(frees(&linker), WasmVal::I32(0)));
+    }
+
+    #[test]
+    fn sizes_beyond_i32_are_rejected() {
+        assert!(Allocator::MallocFree.alloc_call(i32::MAX as u32).is_ok());
+        assert!(Allocator::MallocFree
+            .alloc_call(i32::MAX as u32 + 1)
+            .is_err());
+        assert!(Allocator::CabiRealloc.alloc_call(u32::MAX).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Defines GuestMemory, a bounds-checked view of a guest linear memory.
+
+use std::marker::PhantomData;
+
+use wasmedge_types::error::{InstanceError, WasmEdgeError};
+use wasmedge_types::WasmEdgeResult;
+
+use super::async_mod::AsyncLinker;
+use super::instance::memory::Memory;
+use super::module::AsInstance;
+use super::Linker;
+
+/// The name of the memory a guest exports by default.
+pub const DEFAULT_MEMORY: &str = "memory";
+
+/// Marks types that can be read from and written to guest memory as raw little-endian bytes.
+///
+/// # Safety
+///
+/// Every bit pattern of `size_of::<Self>()` bytes must be a valid value of the type, and the type
+/// must not contain padding or pointers.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A view of a guest linear memory.
+///
+/// The view mutably borrows the linker it was taken from, so the guest cannot be called (and hence
+/// cannot `memory.grow`) while it is alive. Slices are only lent out for the duration of a closure,
+/// so they can never be held across an `.await` point; everything else copies in or out.
+#[derive(Debug)]
+pub struct GuestMemory<'a> {
+    mem: Memory,
+    _linker: PhantomData<&'a mut ()>,
+}
+
+impl GuestMemory<'_> {
+    /// Returns the size of the memory in bytes.
+    pub fn size(&self) -> usize {
+        self.mem.size() as usize * 65536
+    }
+
+    fn check_range(&self, ptr: u32, len: usize) -> WasmEdgeResult<()> {
+        match (ptr as usize).checked_add(len) {
+            Some(end) if end <= self.size() => Ok(()),
+            _ => Err(WasmEdgeError::Operation(format!(
+                "guest memory access out of bounds: {}+{} > {}",
+                ptr,
+                len,
+                self.size()
+            ))),
+        }
+    }
+
+    /// Copies `len` bytes starting at `ptr` out of guest memory.
+    pub fn read_bytes(&self, ptr: u32, len: u32) -> WasmEdgeResult<Vec<u8>> {
+        self.check_range(ptr, len as usize)?;
+        self.mem.get_data(ptr, len)
+    }
+
+    /// Copies `data` into guest memory starting at `ptr`.
+    pub fn write_bytes(&mut self, ptr: u32, data: &[u8]) -> WasmEdgeResult<()> {
+        self.check_range(ptr, data.len())?;
+        self.mem.set_data(data, ptr)
+    }
+
+    /// Reads a value of type `T` at `ptr`.
+    pub fn read<T: Pod>(&self, ptr: u32) -> WasmEdgeResult<T> {
+        self.with_slice(ptr, std::mem::size_of::<T>() as u32, |s| unsafe {
+            std::ptr::read_unaligned(s.as_ptr() as *const T)
+        })
+    }
+
+    /// Writes `val` at `ptr`.
+    pub fn write<T: Pod>(&mut self, ptr: u32, val: T) -> WasmEdgeResult<()> {
+        self.with_slice_mut(ptr, std::mem::size_of::<T>() as u32, |s| unsafe {
+            std::ptr::write_unaligned(s.as_mut_ptr() as *mut T, val)
+        })
+    }
+
+    /// Reads the UTF-8 string of `len` bytes at `ptr`.
+    pub fn read_str(&self, ptr: u32, len: u32) -> WasmEdgeResult<String> {
+        let bytes = self.read_bytes(ptr, len)?;
+        String::from_utf8(bytes).map_err(|e| WasmEdgeError::Operation(e.to_string()))
+    }
+
+    /// Lends the `len` bytes at `ptr` to `f` without copying.
+    pub fn with_slice<R>(
+        &self,
+        ptr: u32,
+        len: u32,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> WasmEdgeResult<R> {
+        self.check_range(ptr, len as usize)?;
+        if len == 0 {
+            return Ok(f(&[]));
+        }
+        let s = self.mem.data_pointer(ptr as usize, len as usize)?;
+        Ok(f(s))
+    }
+
+    /// Lends the `len` bytes at `ptr` to `f` mutably without copying.
+    pub fn with_slice_mut<R>(
+        &mut self,
+        ptr: u32,
+        len: u32,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> WasmEdgeResult<R> {
+        self.check_range(ptr, len as usize)?;
+        if len == 0 {
+            return Ok(f(&mut []));
+        }
+        let s = self.mem.data_pointer_mut(ptr as usize, len as usize)?;
+        Ok(f(s))
+    }
+}
+
+impl Linker {
+    /// Returns a view of the guest's default exported memory.
+    pub fn memory(&mut self) -> WasmEdgeResult<GuestMemory<'_>> {
+        self.memory_named(DEFAULT_MEMORY)
+    }
+
+    /// Returns a view of the exported guest memory `name`.
+    pub fn memory_named(&mut self, name: &str) -> WasmEdgeResult<GuestMemory<'_>> {
+        let mem = match &self.inst {
+            Some(inst) => inst.get_memory(name)?,
+            None => {
+                return Err(WasmEdgeError::Instance(InstanceError::NotFoundMem(
+                    name.to_string(),
+                )))
+            }
+        };
+        Ok(GuestMemory {
+            mem,
+            _linker: PhantomData,
+        })
+    }
+}
+
+impl AsyncLinker {
+    /// Returns a view of the guest's default exported memory.
+    pub fn memory(&mut self) -> WasmEdgeResult<GuestMemory<'_>> {
+        self.real_linker.memory()
+    }
+
+    /// Returns a view of the exported guest memory `name`.
+    pub fn memory_named(&mut self, name: &str) -> WasmEdgeResult<GuestMemory<'_>> {
+        self.real_linker.memory_named(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::AsLinker;
+    use super::*;
+
+    fn linker() -> Box<Linker> {
+        let module = Loader::create(&None)
+            .unwrap()
+            .load_from_wat(r#"(module (memory (export "memory") 1) (data (i32.const 8) "guest"))"#)
+            .unwrap();
+        let mut linker = Linker::new(&None).unwrap();
+        linker.active_module(&module).unwrap();
+        linker
+    }
+
+    #[test]
+    fn reads_and_writes_values() {
+        let mut linker = linker();
+        let mut mem = linker.memory().unwrap();
+        assert_eq!(mem.size(), 65536);
+        assert_eq!(mem.read_str(8, 5).unwrap(), "guest");
+
+        mem.write(1, 0x0102_0304u32).unwrap();
+        assert_eq!(mem.read::<u32>(1).unwrap(), 0x0102_0304);
+        assert_eq!(mem.read_bytes(1, 4).unwrap(), [4, 3, 2, 1]);
+
+        mem.with_slice_mut(8, 1, |s| s[0] = b'G').unwrap();
+        assert_eq!(mem.with_slice(8, 5, |s| s.to_vec()).unwrap(), b"Guest");
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_accesses() {
+        let mut linker = linker();
+        let mut mem = linker.memory().unwrap();
+        assert!(mem.read::<u64>(65532).is_err());
+        assert!(mem.write_bytes(u32::MAX, b"x").is_err());
+        assert!(mem.with_slice(65536, 1, |_| ()).is_err());
+        assert!(mem.with_slice(65536, 0, |s| s.is_empty()).unwrap());
+        assert!(linker.memory_named("missing").is_err());
+    }
+}
@@ -1,5 +1,8 @@
+use crate::async_sdk::error;
 use crate::async_sdk::executor::Executor;
+use crate::async_sdk::instance::memory::Memory;
 use crate::async_sdk::types::WasmVal;
+use crate::async_sdk::wasm_ptr::WasmArgs;
 use core::ffi::c_void;
 use wasmedge_sys::ffi;
 use wasmedge_types::error::{FuncError, WasmEdgeError};
@@ -45,6 +48,72 @@ extern "C" fn wraper_fn<T: Sized>(
     }
 }
 
+/// Returned when the raw arguments do not match the typed signature.
+pub(crate) const FUNC_TYPE_MISMATCH: u8 = 0x83;
+/// Returned when a pointer argument is outside of the caller's default memory.
+pub(crate) const MEMORY_OUT_OF_BOUNDS: u8 = 0x88;
+
+/// Converts the raw arguments of a host call into `A`, checking pointer arguments against the
+/// caller's default memory.
+pub(crate) fn typed_args<A: WasmArgs>(
+    mem_ctx: *mut ffi::WasmEdge_MemoryInstanceContext,
+    params: *const ffi::WasmEdge_Value,
+    param_len: u32,
+) -> Result<A, u8> {
+    let input = {
+        let raw_input = unsafe { std::slice::from_raw_parts(params, param_len as usize) };
+        raw_input
+            .iter()
+            .map(|r| (*r).into())
+            .collect::<Vec<WasmVal>>()
+    };
+    let args = A::from_wasm_vals(&input).ok_or(FUNC_TYPE_MISMATCH)?;
+
+    let mem_size = if mem_ctx.is_null() {
+        0
+    } else {
+        Memory::from_raw(mem_ctx).size() as usize * 65536
+    };
+    match args.in_bounds(mem_size) {
+        true => Ok(args),
+        false => Err(MEMORY_OUT_OF_BOUNDS),
+    }
+}
+
+extern "C" fn typed_wraper_fn<T: Sized, A: WasmArgs>(
+    key_ptr: *mut c_void,
+    data: *mut c_void,
+    mem_ctx: *mut ffi::WasmEdge_MemoryInstanceContext,
+    params: *const ffi::WasmEdge_Value,
+    param_len: u32,
+    returns: *mut ffi::WasmEdge_Value,
+    return_len: u32,
+) -> ffi::WasmEdge_Result {
+    let real_fn: fn(Option<&mut T>, A) -> Result<Vec<WasmVal>, u32> =
+        unsafe { std::mem::transmute(key_ptr) };
+
+    let input = match typed_args::<A>(mem_ctx, params, param_len) {
+        Ok(input) => input,
+        Err(c) => return ffi::WasmEdge_Result { Code: c },
+    };
+
+    let return_len = return_len as usize;
+    let raw_returns = unsafe { std::slice::from_raw_parts_mut(returns, return_len) };
+
+    let data = unsafe { (data as *mut T).as_mut() };
+
+    match real_fn(data, input) {
+        Ok(v) => {
+            assert!(v.len() == return_len);
+            for (idx, item) in v.into_iter().enumerate() {
+                raw_returns[idx] = item.into();
+            }
+            ffi::WasmEdge_Result { Code: 0 }
+        }
+        Err(c) => ffi::WasmEdge_Result { Code: c as u8 },
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Function {
     pub(crate) inner: InnerFunc,
@@ -76,6 +145,32 @@ impl Function {
         }
     }
 
+    pub(crate) fn create_typed<T: Sized, A: WasmArgs>(
+        ty: (Vec<ValType>, Vec<ValType>),
+        real_fn: fn(Option<&mut T>, A) -> Result<Vec<WasmVal>, u32>,
+        data: *mut T,
+        cost: u64,
+    ) -> WasmEdgeResult<Self> {
+        unsafe {
+            let ty = FuncType::create(ty.0, ty.1)?;
+            let ctx = ffi::WasmEdge_FunctionInstanceCreateBinding(
+                ty.inner.0,
+                Some(typed_wraper_fn::<T, A>),
+                real_fn as *mut c_void,
+                data.cast(),
+                cost,
+            );
+            ty.delete();
+
+            match ctx.is_null() {
+                true => Err(WasmEdgeError::Func(FuncError::Create)),
+                false => Ok(Self {
+                    inner: InnerFunc(ctx),
+                }),
+            }
+        }
+    }
+
     pub fn func_type(&self) -> WasmEdgeResult<(Vec<ValType>, Vec<ValType>)> {
         let ty = unsafe { ffi::WasmEdge_FunctionInstanceGetFunctionType(self.inner.0 as *mut _) };
         if ty.is_null() {
@@ -289,7 +384,7 @@ impl FuncRef {
         }
     }
 
-    pub fn call(&self, engine: &mut Executor, args: &[WasmVal]) -> WasmEdgeResult<Vec<WasmVal>> {
+    pub fn call(&self, engine: &mut Executor, args: &[WasmVal]) -> error::Result<Vec<WasmVal>> {
         engine.run_func_ref(self, args)
     }
 }
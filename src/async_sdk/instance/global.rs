@@ -0,0 +1,209 @@
+//! Defines WasmEdge Global and GlobType.
+
+use wasmedge_sys::ffi;
+use wasmedge_types::error::{GlobalError, WasmEdgeError};
+use wasmedge_types::{Mutability, ValType, WasmEdgeResult};
+
+use super::super::types::WasmVal;
+use super::super::wasm_ptr::WasmArg;
+
+/// Defines a WebAssembly global instance, which holds a single value of its [type](crate::GlobType).
+///
+/// A [Global](crate::Global) handle is owned or borrowed, see [ownership](super#ownership).
+#[derive(Debug)]
+pub struct Global {
+    pub(crate) inner: InnerGlobal,
+    pub(crate) owned: bool,
+}
+
+owned_handle!(
+    Global,
+    InnerGlobal,
+    ffi::WasmEdge_GlobalInstanceContext,
+    ffi::WasmEdge_GlobalInstanceDelete
+);
+
+impl Global {
+    /// Creates a new host-defined global with the initial value `val`.
+    ///
+    /// # Error
+    ///
+    /// If `val` does not match the value type of `ty`, or if fail to create, then an error is returned.
+    pub fn create(ty: GlobType, val: WasmVal) -> WasmEdgeResult<Self> {
+        if !val_matches(ty.value_type(), &val) {
+            ty.delete();
+            return Err(WasmEdgeError::Global(GlobalError::UnmatchedValType));
+        }
+        let ctx = unsafe { ffi::WasmEdge_GlobalInstanceCreate(ty.inner.0, val.into()) };
+        ty.delete();
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::Global(GlobalError::Create)),
+            false => Ok(Global {
+                inner: InnerGlobal(ctx),
+                owned: true,
+            }),
+        }
+    }
+
+    /// Returns the value type and the mutability of this global.
+    pub fn get_type(&self) -> WasmEdgeResult<(ValType, Mutability)> {
+        let ty_ctx = unsafe { ffi::WasmEdge_GlobalInstanceGetGlobalType(self.inner.0) };
+        if ty_ctx.is_null() {
+            Err(WasmEdgeError::Global(GlobalError::Type))
+        } else {
+            let ty = GlobType {
+                inner: InnerGlobType(ty_ctx as *mut _),
+            };
+            Ok((ty.value_type(), ty.mutability()))
+        }
+    }
+
+    pub fn get_value(&self) -> WasmVal {
+        unsafe { ffi::WasmEdge_GlobalInstanceGetValue(self.inner.0).into() }
+    }
+
+    /// Returns the value of this global as a `T`.
+    ///
+    /// # Error
+    ///
+    /// If the value type of the global is not `T`, then an error is returned.
+    pub fn get<T: WasmArg>(&self) -> WasmEdgeResult<T> {
+        match T::from_wasm_vals(&[self.get_value()]) {
+            Some((v, _)) => Ok(v),
+            None => Err(WasmEdgeError::Global(GlobalError::UnmatchedValType)),
+        }
+    }
+
+    /// Sets the value of this global.
+    ///
+    /// # Error
+    ///
+    /// If the global is immutable or `val` does not match its value type, then an error is returned.
+    pub fn set_value(&mut self, val: WasmVal) -> WasmEdgeResult<()> {
+        let (val_ty, mutability) = self.get_type()?;
+        if mutability == Mutability::Const {
+            return Err(WasmEdgeError::Global(GlobalError::ModifyConst));
+        }
+        if !val_matches(val_ty, &val) {
+            return Err(WasmEdgeError::Global(GlobalError::UnmatchedValType));
+        }
+        unsafe { ffi::WasmEdge_GlobalInstanceSetValue(self.inner.0, val.into()) };
+        Ok(())
+    }
+
+    pub fn set<T: Into<WasmVal>>(&mut self, val: T) -> WasmEdgeResult<()> {
+        self.set_value(val.into())
+    }
+}
+
+fn val_matches(ty: ValType, val: &WasmVal) -> bool {
+    matches!(
+        (ty, val),
+        (ValType::I32, WasmVal::I32(_))
+            | (ValType::I64, WasmVal::I64(_))
+            | (ValType::F32, WasmVal::F32(_))
+            | (ValType::F64, WasmVal::F64(_))
+            | (ValType::V128, WasmVal::V128(_))
+            | (ValType::FuncRef, WasmVal::FuncRef(_))
+            | (ValType::FuncRef, WasmVal::None)
+            | (ValType::ExternRef, WasmVal::ExternRef(_))
+            | (ValType::ExternRef, WasmVal::None)
+    )
+}
+
+#[derive(Debug)]
+pub(crate) struct InnerGlobal(pub(crate) *mut ffi::WasmEdge_GlobalInstanceContext);
+unsafe impl Send for InnerGlobal {}
+unsafe impl Sync for InnerGlobal {}
+
+/// Defines the type of a wasm global instance
+#[derive(Debug)]
+pub struct GlobType {
+    pub(crate) inner: InnerGlobType,
+}
+impl GlobType {
+    pub fn create(val_ty: ValType, mutability: Mutability) -> WasmEdgeResult<Self> {
+        let ctx = unsafe { ffi::WasmEdge_GlobalTypeCreate(val_ty.into(), mutability.into()) };
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::GlobalTypeCreate),
+            false => Ok(Self {
+                inner: InnerGlobType(ctx),
+            }),
+        }
+    }
+
+    pub fn value_type(&self) -> ValType {
+        unsafe { ffi::WasmEdge_GlobalTypeGetValType(self.inner.0).into() }
+    }
+
+    pub fn mutability(&self) -> Mutability {
+        unsafe { ffi::WasmEdge_GlobalTypeGetMutability(self.inner.0).into() }
+    }
+
+    pub(crate) fn delete(self) {
+        if !self.inner.0.is_null() {
+            unsafe { ffi::WasmEdge_GlobalTypeDelete(self.inner.0) }
+        }
+    }
+}
+
+impl From<GlobType> for wasmedge_types::GlobalType {
+    fn from(ty: GlobType) -> Self {
+        wasmedge_types::GlobalType::new(ty.value_type(), ty.mutability())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InnerGlobType(pub(crate) *mut ffi::WasmEdge_GlobalTypeContext);
+unsafe impl Send for InnerGlobType {}
+unsafe impl Sync for InnerGlobType {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::config::Config;
+    use super::super::super::{AsLinker, Linker};
+    use super::*;
+
+    fn global(mutability: Mutability) -> Global {
+        Global::create(
+            GlobType::create(ValType::I32, mutability).unwrap(),
+            WasmVal::I32(1),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn created_globals_are_owned() {
+        let global = global(Mutability::Var);
+        assert!(global.is_owned());
+        let borrowed = Global::from_raw(global.inner.0);
+        assert!(!borrowed.is_owned());
+        drop(borrowed);
+        assert!(matches!(global.get_value(), WasmVal::I32(1)));
+    }
+
+    #[test]
+    fn create_checks_the_value_type() {
+        let ty = GlobType::create(ValType::I64, Mutability::Const).unwrap();
+        assert!(Global::create(ty, WasmVal::I32(1)).is_err());
+    }
+
+    #[test]
+    fn mutable_host_globals_need_the_proposal() {
+        let mut config = Config::create().unwrap();
+        config.mutable_globals(false);
+        let mut linker = Linker::new(&Some(config)).unwrap();
+        let r = linker.new_import_object("env", &mut |builder| {
+            builder.add_global("count", ValType::I32, Mutability::Var, WasmVal::I32(0))
+        });
+        assert!(matches!(
+            r,
+            Err(WasmEdgeError::Global(GlobalError::ModifyConst))
+        ));
+        linker
+            .new_import_object("env", &mut |builder| {
+                builder.add_global("size", ValType::I32, Mutability::Const, WasmVal::I32(0))
+            })
+            .unwrap();
+    }
+}
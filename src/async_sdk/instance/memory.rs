@@ -5,18 +5,22 @@ use wasmedge_types::error::{MemError, WasmEdgeError};
 use wasmedge_types::{MemoryType, WasmEdgeResult};
 
 /// Defines a WebAssembly memory instance, which is a linear memory described by its [type](crate::MemType). Each memory instance consists of a vector of bytes and an optional maximum size, and its size is a multiple of the WebAssembly page size (*64KiB* of each page).
+///
+/// A [Memory](crate::Memory) handle is owned or borrowed, see [ownership](super#ownership).
 #[derive(Debug)]
 pub struct Memory {
     pub(crate) inner: InnerMemory,
+    pub(crate) owned: bool,
 }
 
-impl Memory {
-    pub fn from_raw(raw_ptr: *mut ffi::WasmEdge_MemoryInstanceContext) -> Self {
-        Memory {
-            inner: InnerMemory(raw_ptr),
-        }
-    }
+owned_handle!(
+    Memory,
+    InnerMemory,
+    ffi::WasmEdge_MemoryInstanceContext,
+    ffi::WasmEdge_MemoryInstanceDelete
+);
 
+impl Memory {
     pub fn create(ty: MemType) -> WasmEdgeResult<Self> {
         let ctx = unsafe { ffi::WasmEdge_MemoryInstanceCreate(ty.inner.0 as *const _) };
         ty.delete();
@@ -24,6 +28,7 @@ impl Memory {
             true => Err(WasmEdgeError::Mem(MemError::Create)),
             false => Ok(Memory {
                 inner: InnerMemory(ctx),
+                owned: true,
             }),
         }
     }
@@ -130,10 +135,6 @@ impl Memory {
     pub fn grow(&mut self, count: u32) -> WasmEdgeResult<()> {
         unsafe { check(ffi::WasmEdge_MemoryInstanceGrowPage(self.inner.0, count)) }
     }
-
-    pub fn delete(self) {
-        unsafe { ffi::WasmEdge_MemoryInstanceDelete(self.inner.0) };
-    }
 }
 
 #[derive(Debug)]
@@ -200,3 +201,35 @@ impl From<MemType> for wasmedge_types::MemoryType {
 pub(crate) struct InnerMemType(pub(crate) *mut ffi::WasmEdge_MemoryTypeContext);
 unsafe impl Send for InnerMemType {}
 unsafe impl Sync for InnerMemType {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::module::ImportModule;
+    use super::*;
+
+    fn memory() -> Memory {
+        Memory::create(MemType::create(1, Some(2), false).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn created_memories_are_owned() {
+        let mem = memory();
+        assert!(mem.is_owned());
+
+        let borrowed = Memory::from_raw(mem.inner.0);
+        assert!(!borrowed.is_owned());
+        // dropping the borrowed handle leaves the memory alive
+        drop(borrowed);
+        assert_eq!(mem.size(), 1);
+    }
+
+    #[test]
+    fn import_modules_take_only_owned_memories() {
+        let mem = memory();
+        let mut import = ImportModule::create("env").unwrap();
+        assert!(import
+            .add_memory("borrowed", Memory::from_raw(mem.inner.0))
+            .is_err());
+        import.add_memory("memory", mem).unwrap();
+    }
+}
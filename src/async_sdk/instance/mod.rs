@@ -1,2 +1,59 @@
+//! Defines the WasmEdge instances a module exports or a host provides.
+//!
+//! # Ownership
+//!
+//! A [Memory](memory::Memory), [Global](global::Global) or [Table](table::Table) handle is either owned
+//! or borrowed. Handles made by `create` are owned and free their instance on drop, unless they are
+//! handed over to an import module, which then frees the instance together with the module. Handles
+//! found on a module instance are borrowed from it: they never free the instance and cannot be handed
+//! over to an import module.
+
+use wasmedge_types::error::WasmEdgeError;
+
+/// Implements the [ownership](self#ownership) rules for the handle `$handle` of the instance `$ctx`,
+/// freed with `$delete`.
+macro_rules! owned_handle {
+    ($handle:ident, $inner:ident, $ctx:ty, $delete:path) => {
+        impl Drop for $handle {
+            fn drop(&mut self) {
+                if self.owned && !self.inner.0.is_null() {
+                    unsafe { $delete(self.inner.0) };
+                }
+            }
+        }
+
+        impl $handle {
+            /// Creates a borrowed handle of an instance owned elsewhere.
+            pub fn from_raw(raw_ptr: *mut $ctx) -> Self {
+                $handle {
+                    inner: $inner(raw_ptr),
+                    owned: false,
+                }
+            }
+
+            /// Checks if this handle owns its instance.
+            pub fn is_owned(&self) -> bool {
+                self.owned
+            }
+
+            /// Gives up ownership of the instance, returning the raw pointer.
+            pub(crate) fn into_raw(mut self) -> *mut $ctx {
+                self.owned = false;
+                self.inner.0
+            }
+        }
+    };
+}
+
 pub mod function;
+pub mod global;
 pub mod memory;
+pub mod table;
+
+/// Returns the error of handing the borrowed `kind` instance `name` over to an import module.
+pub(crate) fn borrowed_error(kind: &str, name: &str) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!(
+        "{} {} is borrowed from another module instance",
+        kind, name
+    ))
+}
@@ -0,0 +1,195 @@
+//! Defines WasmEdge Table and TableType.
+
+use super::super::types::WasmVal;
+use super::super::utils::check;
+use super::function::FuncRef;
+use wasmedge_sys::ffi;
+use wasmedge_types::error::{TableError, WasmEdgeError};
+use wasmedge_types::{RefType, WasmEdgeResult};
+
+/// Defines a WebAssembly table instance, a vector of references described by its [type](crate::TableType).
+///
+/// Tables of `funcref` let a guest call functions indirectly by index, which is how host code
+/// registers guest callbacks.
+///
+/// A [Table](crate::Table) handle is owned or borrowed, see [ownership](super#ownership).
+#[derive(Debug)]
+pub struct Table {
+    pub(crate) inner: InnerTable,
+    pub(crate) owned: bool,
+}
+
+owned_handle!(
+    Table,
+    InnerTable,
+    ffi::WasmEdge_TableInstanceContext,
+    ffi::WasmEdge_TableInstanceDelete
+);
+
+impl Table {
+    pub fn create(ty: TableType) -> WasmEdgeResult<Self> {
+        let ctx = unsafe { ffi::WasmEdge_TableInstanceCreate(ty.inner.0) };
+        ty.delete();
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::Table(TableError::Create)),
+            false => Ok(Table {
+                inner: InnerTable(ctx),
+                owned: true,
+            }),
+        }
+    }
+
+    /// Returns the reference type and the limit of this table.
+    pub fn get_type(&self) -> WasmEdgeResult<(RefType, u32, Option<u32>)> {
+        let ty_ctx = unsafe { ffi::WasmEdge_TableInstanceGetTableType(self.inner.0) };
+        if ty_ctx.is_null() {
+            Err(WasmEdgeError::Table(TableError::Type))
+        } else {
+            let ty = TableType {
+                inner: InnerTableType(ty_ctx as *mut _),
+            };
+            let (min, max) = ty.limit();
+            Ok((ty.elem_type(), min, max))
+        }
+    }
+
+    /// Returns the element at `idx`.
+    pub fn get(&self, idx: u32) -> WasmEdgeResult<WasmVal> {
+        let mut val = unsafe { ffi::WasmEdge_ValueGenI32(0) };
+        unsafe {
+            check(ffi::WasmEdge_TableInstanceGetData(
+                self.inner.0,
+                &mut val,
+                idx,
+            ))?
+        };
+        Ok(val.into())
+    }
+
+    /// Sets the element at `idx`, which must be a reference of the table's type or [WasmVal::None].
+    pub fn set(&mut self, idx: u32, val: WasmVal) -> WasmEdgeResult<()> {
+        let val = match val {
+            WasmVal::None => {
+                let (ref_ty, _, _) = self.get_type()?;
+                let ty: ffi::WasmEdge_RefType = ref_ty.into();
+                unsafe { ffi::WasmEdge_ValueGenNullRef(ty) }
+            }
+            val => val.into(),
+        };
+        unsafe { check(ffi::WasmEdge_TableInstanceSetData(self.inner.0, val, idx)) }
+    }
+
+    /// Returns the function referenced by the element at `idx`.
+    ///
+    /// # Error
+    ///
+    /// If the element is not a function reference or is null, then an error is returned.
+    pub fn get_func(&self, idx: u32) -> WasmEdgeResult<FuncRef> {
+        match self.get(idx)? {
+            WasmVal::FuncRef(func) if !func.inner.0.is_null() => Ok(func),
+            _ => Err(WasmEdgeError::Operation(format!(
+                "no function at table index {}",
+                idx
+            ))),
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn size(&self) -> u32 {
+        unsafe { ffi::WasmEdge_TableInstanceGetSize(self.inner.0) }
+    }
+
+    /// Grows the table by `count` null elements.
+    pub fn grow(&mut self, count: u32) -> WasmEdgeResult<()> {
+        unsafe { check(ffi::WasmEdge_TableInstanceGrow(self.inner.0, count)) }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InnerTable(pub(crate) *mut ffi::WasmEdge_TableInstanceContext);
+unsafe impl Send for InnerTable {}
+unsafe impl Sync for InnerTable {}
+
+/// Defines the type of a wasm table instance
+#[derive(Debug)]
+pub struct TableType {
+    pub(crate) inner: InnerTableType,
+}
+impl TableType {
+    pub fn create(elem_ty: RefType, min: u32, max: Option<u32>) -> WasmEdgeResult<Self> {
+        let ctx = unsafe {
+            ffi::WasmEdge_TableTypeCreate(
+                elem_ty.into(),
+                ffi::WasmEdge_Limit {
+                    HasMax: max.is_some(),
+                    Shared: false,
+                    Min: min,
+                    Max: max.unwrap_or(min),
+                },
+            )
+        };
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::TableTypeCreate),
+            false => Ok(Self {
+                inner: InnerTableType(ctx),
+            }),
+        }
+    }
+
+    pub fn elem_type(&self) -> RefType {
+        unsafe { ffi::WasmEdge_TableTypeGetRefType(self.inner.0).into() }
+    }
+
+    pub fn limit(&self) -> (u32, Option<u32>) {
+        let limit = unsafe { ffi::WasmEdge_TableTypeGetLimit(self.inner.0) };
+        (limit.Min, if limit.HasMax { Some(limit.Max) } else { None })
+    }
+
+    pub(crate) fn delete(self) {
+        if !self.inner.0.is_null() {
+            unsafe { ffi::WasmEdge_TableTypeDelete(self.inner.0) }
+        }
+    }
+}
+
+impl From<TableType> for wasmedge_types::TableType {
+    fn from(ty: TableType) -> Self {
+        let (min, max) = ty.limit();
+        wasmedge_types::TableType::new(ty.elem_type(), min, max)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InnerTableType(pub(crate) *mut ffi::WasmEdge_TableTypeContext);
+unsafe impl Send for InnerTableType {}
+unsafe impl Sync for InnerTableType {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::module::ImportModule;
+    use super::*;
+
+    fn table() -> Table {
+        Table::create(TableType::create(RefType::FuncRef, 2, Some(4)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn created_tables_are_owned() {
+        let table = table();
+        assert!(table.is_owned());
+        let borrowed = Table::from_raw(table.inner.0);
+        assert!(!borrowed.is_owned());
+        drop(borrowed);
+        assert_eq!(table.size(), 2);
+    }
+
+    #[test]
+    fn import_modules_take_only_owned_tables() {
+        let table = table();
+        let mut import = ImportModule::create("env").unwrap();
+        assert!(import
+            .add_table("borrowed", Table::from_raw(table.inner.0))
+            .is_err());
+        import.add_table("table", table).unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+//! Defines call deadlines and [InterruptHandle], which stop a running guest call from another thread.
+//!
+//! The guest runs on the calling thread, so it is stopped where it checks the deadline and the
+//! handle itself: modules loaded with [Loader::set_interrupt_checks](super::ast_module::Loader::set_interrupt_checks)
+//! call the `wasmedge_interrupt.check` import every few loop iterations, which
+//! [Linker] registers when it instantiates such a module, sync or async. An async call also checks
+//! them each time it is resumed and each time it calls an async host function.
+//!
+//! A guest loaded without interrupt checks is only stopped by an async call's own checks, so a loop
+//! in a sync call, or in an async call between two async host calls, runs to its end.
+//!
+//! A stopped call fails with an [Error](super::error::Error) whose source is [Timeout].
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use wasmedge_types::WasmEdgeResult;
+
+use super::ast_module::AstModule;
+use super::async_mod::AsyncLinker;
+use super::error::{self, Error, Operation};
+use super::executor::Executor;
+use super::fuel::{inject_loop_checks, LoopCheck};
+use super::module::ImportModule;
+use super::types::WasmVal;
+use super::Linker;
+
+/// The WasmEdge result code of `Interrupted`.
+pub(crate) const INTERRUPTED: u32 = 0x07;
+
+/// The module of the import interrupt checks call.
+pub const INTERRUPT_MODULE: &str = "wasmedge_interrupt";
+
+/// The name of the import interrupt checks call.
+pub const INTERRUPT_CHECK: &str = "check";
+
+/// The largest number of loop iterations between two interrupt checks.
+pub const MAX_INTERRUPT_INTERVAL: u32 = i32::MAX as u32;
+
+/// The error source of a call that was stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// The call ran past its deadline, the timeout of the call.
+    Deadline(Duration),
+    /// The call was stopped through an [InterruptHandle].
+    Interrupted,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timeout::Deadline(timeout) => write!(f, "call timed out after {:?}", timeout),
+            Timeout::Interrupted => write!(f, "call was interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// Stops the running call of a linker; it can be cloned and sent to other threads.
+///
+/// Interrupting while no call is running has no effect on the next call.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct InterruptState {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    flag: Option<Arc<AtomicBool>>,
+    /// The reason the running call was stopped.
+    reason: Option<Timeout>,
+}
+
+impl InterruptState {
+    fn check(&self) -> Option<Timeout> {
+        if let Some(flag) = &self.flag {
+            if flag.load(Ordering::SeqCst) {
+                return Some(Timeout::Interrupted);
+            }
+        }
+        match (self.deadline, self.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Some(Timeout::Deadline(timeout))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Executor {
+    /// Starts the deadline of a call and clears earlier interrupts.
+    pub(crate) fn arm_interrupt(&mut self) {
+        let state = &mut self.interrupt;
+        state.deadline = state.timeout.map(|timeout| Instant::now() + timeout);
+        state.reason = None;
+        if let Some(flag) = &state.flag {
+            flag.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Ends the deadline of the call, so the calls made while tearing it down are not stopped.
+    pub(crate) fn disarm_interrupt(&mut self) {
+        self.interrupt.deadline = None;
+    }
+
+    /// Returns `true` if the running call must be stopped, remembering why.
+    pub(crate) fn poll_interrupt(&mut self) -> bool {
+        let state = &mut self.interrupt;
+        if state.reason.is_none() {
+            state.reason = state.check();
+        }
+        state.reason.is_some()
+    }
+}
+
+/// Adds an interrupt check to every loop of `wasm`, made every `interval` loop iterations.
+pub(crate) fn inject_interrupt_checks(wasm: &[u8], interval: u32) -> error::Result<Vec<u8>> {
+    inject_loop_checks(
+        wasm,
+        LoopCheck {
+            module: INTERRUPT_MODULE,
+            name: INTERRUPT_CHECK,
+            interval,
+        },
+    )
+}
+
+/// The `wasmedge_interrupt.check` import, which fails the running call if it must be stopped.
+fn interrupt_check(linker: Option<&mut Linker>, _args: &[WasmVal]) -> Result<Vec<WasmVal>, u32> {
+    match linker {
+        Some(linker) if linker.executor.poll_interrupt() => Err(INTERRUPTED),
+        _ => Ok(vec![]),
+    }
+}
+
+impl Linker {
+    /// Sets the timeout of each call; `None` removes it.
+    ///
+    /// A call that runs past it fails with an [Error] whose source is [Timeout::Deadline].
+    pub fn set_call_timeout(&mut self, timeout: Option<Duration>) {
+        self.executor.interrupt.timeout = timeout;
+    }
+
+    pub fn call_timeout(&self) -> Option<Duration> {
+        self.executor.interrupt.timeout
+    }
+
+    /// Returns a handle that stops the running call, which then fails with an [Error] whose source
+    /// is [Timeout::Interrupted].
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        let flag = self
+            .executor
+            .interrupt
+            .flag
+            .get_or_insert_with(|| Arc::new(AtomicBool::new(false)));
+        InterruptHandle { flag: flag.clone() }
+    }
+
+    /// Fails if the running call must be stopped, e.g. before resuming it.
+    pub(crate) fn check_interrupt(&mut self, name: &str) -> error::Result<()> {
+        self.executor.poll_interrupt();
+        match self.stopped(name) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Registers the import interrupt checks call, if `module` imports it and it is not registered
+    /// yet.
+    pub(crate) fn register_interrupt_check(&mut self, module: &AstModule) -> WasmEdgeResult<()> {
+        let imported = module
+            .imports()
+            .iter()
+            .any(|import| import.module == INTERRUPT_MODULE && import.name == INTERRUPT_CHECK);
+        if !imported || self.executor.import_module(INTERRUPT_MODULE).is_some() {
+            return Ok(());
+        }
+
+        let mut import_obj = ImportModule::create(INTERRUPT_MODULE)?;
+        let linker: *mut Linker = self;
+        import_obj.add_func(
+            INTERRUPT_CHECK,
+            linker,
+            (vec![], vec![]),
+            interrupt_check,
+            0,
+        )?;
+        self.register_import(import_obj)
+    }
+
+    pub(crate) fn timed_out(&self, name: &str, e: Error) -> Error {
+        self.stopped(name).unwrap_or(e)
+    }
+
+    fn stopped(&self, name: &str) -> Option<Error> {
+        self.executor.interrupt.reason.map(|reason| {
+            Error::new(Operation::Call, reason)
+                .with_name(name)
+                .with_code(INTERRUPTED)
+        })
+    }
+}
+
+impl AsyncLinker {
+    /// Sets the timeout of each call, see [Linker::set_call_timeout].
+    ///
+    /// The deadline of an async call starts when it is first polled, and covers the time it spends
+    /// suspended; a call past its deadline fails when it is next resumed or calls the host.
+    pub fn set_call_timeout(&mut self, timeout: Option<Duration>) {
+        self.real_linker.set_call_timeout(timeout)
+    }
+
+    pub fn call_timeout(&self) -> Option<Duration> {
+        self.real_linker.call_timeout()
+    }
+
+    /// Returns a handle that stops the running call, see [Linker::interrupt_handle].
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        self.real_linker.interrupt_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+    use std::thread;
+
+    use super::super::ast_module::Loader;
+    use super::super::AsLinker;
+    use super::*;
+
+    const SPIN: &str = r#"(module
+        (func (export "spin")
+            (loop $forever (br $forever))))"#;
+
+    fn load(interval: Option<u32>) -> AstModule {
+        let mut loader = Loader::create(&None).unwrap();
+        loader.set_interrupt_checks(interval).unwrap();
+        loader.load_from_wat(SPIN).unwrap()
+    }
+
+    fn linker(module: &AstModule) -> Box<Linker> {
+        let mut linker = Linker::new(&None).unwrap();
+        linker.active_module(module).unwrap();
+        linker
+    }
+
+    fn timeout(e: &Error) -> Timeout {
+        *e.source().unwrap().downcast_ref::<Timeout>().unwrap()
+    }
+
+    #[test]
+    fn interrupt_checks_are_opt_in() {
+        let has_check = |module: &AstModule| {
+            module
+                .imports()
+                .iter()
+                .any(|import| import.module == INTERRUPT_MODULE && import.name == INTERRUPT_CHECK)
+        };
+        assert!(!has_check(&load(None)));
+        assert!(has_check(&load(Some(100))));
+
+        let mut loader = Loader::create(&None).unwrap();
+        assert!(loader
+            .set_interrupt_checks(Some(MAX_INTERRUPT_INTERVAL + 1))
+            .is_err());
+        assert_eq!(loader.get_interrupt_checks(), None);
+    }
+
+    #[test]
+    fn deadline_stops_a_sync_loop() {
+        let mut linker = linker(&load(Some(100)));
+        linker.set_call_timeout(Some(Duration::from_millis(50)));
+        let e = linker.run("spin", &[]).unwrap_err();
+        assert_eq!(e.code(), Some(INTERRUPTED));
+        assert_eq!(timeout(&e), Timeout::Deadline(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn handle_stops_a_sync_loop() {
+        let mut linker = linker(&load(Some(100)));
+        let handle = linker.interrupt_handle();
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+        let e = linker.run("spin", &[]).unwrap_err();
+        interrupter.join().unwrap();
+        assert_eq!(timeout(&e), Timeout::Interrupted);
+    }
+}
@@ -0,0 +1,239 @@
+//! Defines per-linker resource limits and memory growth notifications.
+//!
+//! The limits hold when the guest grows a memory or a table, so the guest sees `memory.grow` or
+//! `table.grow` fail and return -1 instead of the call failing afterwards:
+//! - `max_memory_pages` caps the maximum page count of the config the executor runs with.
+//! - WasmEdge has no cap on tables, so `max_table_elements` is checked against the maximum size every
+//!   table of a module declares before the module is instantiated.
+//!
+//! The [MemoryGrowthHook] is for accounting only: it learns of the growth once a top-level call
+//! returns or an async call suspends, and cannot undo it.
+
+use std::collections::HashMap;
+
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+use wasmparser::{Parser, Payload, TypeRef};
+
+use super::ast_module::AstModule;
+use super::async_mod::AsyncLinker;
+use super::error::{self, Context, Operation};
+use super::module::AsInstance;
+use super::Linker;
+
+/// Resource limits applied to a single [Linker] or [AsyncLinker].
+///
+/// `None` means unlimited, apart from what the [Config](super::config::Config) already enforces.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// The maximum number of pages of each memory of the active instance. A module whose memory
+    /// starts larger fails to instantiate, and `memory.grow` past it returns -1.
+    pub max_memory_pages: Option<u32>,
+    /// The maximum number of elements of each table of the active instance. A module with a table
+    /// that declares no maximum size, or a larger one, is refused by
+    /// [active_module](super::AsLinker::active_module), so `table.grow` past it returns -1.
+    pub max_table_elements: Option<u32>,
+    /// The maximum number of module instances, import modules included, registered in the linker.
+    pub max_instances: Option<u32>,
+}
+
+/// Called with the memory name, the old and the new page count when a guest memory has grown.
+pub type MemoryGrowthHook = Box<dyn FnMut(&str, u32, u32) + Send>;
+
+#[derive(Default)]
+pub(crate) struct LimitState {
+    pub(crate) limits: ResourceLimits,
+    pub(crate) instances: u32,
+    memory_pages: HashMap<String, u32>,
+    on_memory_grow: Option<MemoryGrowthHook>,
+}
+
+fn limit_exceeded(what: &str, value: impl std::fmt::Display, limit: u32) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("{} limit exceeded: {} > {}", what, value, limit))
+}
+
+/// Returns the maximum size each table of `wasm` declares, imported tables included.
+///
+/// Modules that fail to parse have no tables; WasmEdge reports the error when loading them.
+pub(crate) fn table_maximums(wasm: &[u8]) -> Vec<Option<u64>> {
+    let mut maximums = vec![];
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload {
+            Ok(Payload::ImportSection(reader)) => {
+                for import in reader.into_iter().flatten() {
+                    if let TypeRef::Table(ty) = import.ty {
+                        maximums.push(ty.maximum);
+                    }
+                }
+            }
+            Ok(Payload::TableSection(reader)) => {
+                maximums.extend(reader.into_iter().flatten().map(|table| table.ty.maximum));
+            }
+            Ok(_) => {}
+            Err(_) => return vec![],
+        }
+    }
+    maximums
+}
+
+impl Linker {
+    pub fn limits(&self) -> &ResourceLimits {
+        &self.limit_state.limits
+    }
+
+    /// Sets the callback notified when a guest memory has grown.
+    ///
+    /// Growth is observed after every top-level call into the guest, and after every poll of an
+    /// async call, so the hook sees the net change of a call. It cannot reject the growth; use
+    /// [ResourceLimits::max_memory_pages] to stop it.
+    pub fn on_memory_grow(&mut self, hook: MemoryGrowthHook) {
+        self.limit_state.on_memory_grow = Some(hook);
+    }
+
+    /// Counts a new module instance against `max_instances`.
+    pub(crate) fn add_instance(&mut self) -> WasmEdgeResult<()> {
+        let state = &mut self.limit_state;
+        if let Some(max) = state.limits.max_instances {
+            if state.instances >= max {
+                return Err(limit_exceeded("instance", state.instances + 1, max));
+            }
+        }
+        state.instances += 1;
+        Ok(())
+    }
+
+    /// Checks that every table of `module` declares a maximum size within `max_table_elements`.
+    pub(crate) fn check_tables(&self, module: &AstModule) -> WasmEdgeResult<()> {
+        let max = match self.limit_state.limits.max_table_elements {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        for maximum in &module.table_maximums {
+            match maximum {
+                Some(size) if *size <= max as u64 => {}
+                Some(size) => return Err(limit_exceeded("table element", size, max)),
+                None => return Err(limit_exceeded("table element", "unbounded", max)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the memory sizes of the fresh active instance, from which growth is reported.
+    pub(crate) fn track_memories(&mut self) -> WasmEdgeResult<()> {
+        self.limit_state.memory_pages.clear();
+        self.report_memory_growth()
+    }
+
+    /// Notifies the memory growth hook of every memory of the active instance that grew since the
+    /// last report.
+    fn report_memory_growth(&mut self) -> WasmEdgeResult<()> {
+        let inst = match &self.inst {
+            Some(inst) => inst,
+            None => return Ok(()),
+        };
+        let state = &mut self.limit_state;
+        for name in inst.mem_names().unwrap_or_default() {
+            let pages = inst.get_memory(&name)?.size();
+            let old_pages = state.memory_pages.insert(name.clone(), pages);
+            if let (Some(old_pages), Some(hook)) = (old_pages, state.on_memory_grow.as_mut()) {
+                if pages > old_pages {
+                    hook(&name, old_pages, pages);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports the memory growth once the top-level call `name` returns or suspends.
+    pub(crate) fn report_call_growth(&mut self, name: &str) -> error::Result<()> {
+        self.report_memory_growth()
+            .context(Operation::Call)
+            .map_err(|e| e.with_name(name))
+    }
+}
+
+impl AsyncLinker {
+    pub fn limits(&self) -> &ResourceLimits {
+        self.real_linker.limits()
+    }
+
+    /// Sets the callback notified when a guest memory has grown, see [Linker::on_memory_grow].
+    pub fn on_memory_grow(&mut self, hook: MemoryGrowthHook) {
+        self.real_linker.on_memory_grow(hook)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::super::ast_module::Loader;
+    use super::super::types::WasmVal;
+    use super::super::AsLinker;
+    use super::*;
+
+    fn load(wat: &str) -> AstModule {
+        Loader::create(&None).unwrap().load_from_wat(wat).unwrap()
+    }
+
+    #[test]
+    fn reads_table_maximums() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "t" (table 1 8 funcref))
+                (table 1 funcref)
+                (table 2 4 externref))"#,
+        )
+        .unwrap();
+        assert_eq!(table_maximums(&wasm), [Some(8), None, Some(4)]);
+        assert!(table_maximums(b"\0asm").is_empty());
+    }
+
+    #[test]
+    fn tables_must_declare_a_bounded_maximum() {
+        let limits = ResourceLimits {
+            max_table_elements: Some(4),
+            ..Default::default()
+        };
+        let mut linker = Linker::new_with_limits(&None, limits).unwrap();
+        assert!(linker
+            .active_module(&load("(module (table 1 funcref))"))
+            .is_err());
+        assert!(linker
+            .active_module(&load("(module (table 1 8 funcref))"))
+            .is_err());
+        linker
+            .active_module(&load("(module (table 1 4 funcref))"))
+            .unwrap();
+    }
+
+    #[test]
+    fn memory_grow_past_the_limit_fails_in_the_guest() {
+        let limits = ResourceLimits {
+            max_memory_pages: Some(2),
+            ..Default::default()
+        };
+        let mut linker = Linker::new_with_limits(&None, limits).unwrap();
+        linker
+            .active_module(&load(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "grow") (param i32) (result i32)
+                        (memory.grow (local.get 0))))"#,
+            ))
+            .unwrap();
+        let grown: Arc<Mutex<Vec<(String, u32, u32)>>> = Arc::default();
+        let log = grown.clone();
+        linker.on_memory_grow(Box::new(move |name, old, new| {
+            log.lock().unwrap().push((name.to_string(), old, new))
+        }));
+
+        let r = linker.run("grow", &[WasmVal::I32(2)]).unwrap();
+        assert!(matches!(r.as_slice(), [WasmVal::I32(-1)]));
+        assert!(grown.lock().unwrap().is_empty());
+
+        let r = linker.run("grow", &[WasmVal::I32(1)]).unwrap();
+        assert!(matches!(r.as_slice(), [WasmVal::I32(1)]));
+        assert_eq!(*grown.lock().unwrap(), [("memory".to_string(), 1, 2)]);
+    }
+}
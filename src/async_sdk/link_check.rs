@@ -0,0 +1,205 @@
+//! Defines the pre-instantiation link check of an [AstModule] against the registered import modules.
+
+use std::fmt;
+
+use wasmedge_types::{GlobalType, MemoryType, TableType, ValType};
+
+use super::ast_module::{AstModule, ExternalType};
+use super::async_mod::AsyncLinker;
+use super::module::{AsInstance, ImportModule};
+use super::Linker;
+
+/// A single problem found by the link check.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkIssue {
+    /// No import module with this name is registered.
+    UnknownModule {
+        module: String,
+        name: String,
+        expected: ExternalType,
+    },
+    /// The import module is registered but does not provide this field.
+    UnknownImport {
+        module: String,
+        name: String,
+        expected: ExternalType,
+    },
+    /// The import module provides the field with an incompatible type.
+    IncompatibleImportType {
+        module: String,
+        name: String,
+        expected: ExternalType,
+        provided: ExternalType,
+    },
+}
+
+impl fmt::Display for LinkIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkIssue::UnknownModule {
+                module,
+                name,
+                expected,
+            } => write!(
+                f,
+                "{}.{}: module {} is not registered, expected {}",
+                module, name, module, expected
+            ),
+            LinkIssue::UnknownImport {
+                module,
+                name,
+                expected,
+            } => write!(f, "{}.{}: missing, expected {}", module, name, expected),
+            LinkIssue::IncompatibleImportType {
+                module,
+                name,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "{}.{}: expected {}, provided {}",
+                module, name, expected, provided
+            ),
+        }
+    }
+}
+
+/// Every problem found by the link check of one module.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LinkReport {
+    pub issues: Vec<LinkIssue>,
+}
+
+impl LinkReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} unresolved import(s)", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LinkReport {}
+
+fn func_sig(ty: &wasmedge_types::FuncType) -> (Vec<ValType>, Vec<ValType>) {
+    (
+        ty.args().map(|args| args.to_vec()).unwrap_or_default(),
+        ty.returns()
+            .map(|returns| returns.to_vec())
+            .unwrap_or_default(),
+    )
+}
+
+fn limits_match(expected: (u32, Option<u32>), provided: (u32, Option<u32>)) -> bool {
+    provided.0 >= expected.0
+        && match (expected.1, provided.1) {
+            (None, _) => true,
+            (Some(expected_max), Some(provided_max)) => provided_max <= expected_max,
+            (Some(_), None) => false,
+        }
+}
+
+fn types_match(expected: &ExternalType, provided: &ExternalType) -> bool {
+    match (expected, provided) {
+        (ExternalType::Func(e), ExternalType::Func(p)) => func_sig(e) == func_sig(p),
+        (ExternalType::Table(e), ExternalType::Table(p)) => {
+            e.elem_ty() == p.elem_ty()
+                && limits_match((e.minimum(), e.maximum()), (p.minimum(), p.maximum()))
+        }
+        (ExternalType::Memory(e), ExternalType::Memory(p)) => {
+            e.shared() == p.shared()
+                && limits_match((e.minimum(), e.maximum()), (p.minimum(), p.maximum()))
+        }
+        (ExternalType::Global(e), ExternalType::Global(p)) => e == p,
+        _ => false,
+    }
+}
+
+/// Returns the type of the field `name` of `import`, looked up as the same kind of extern as `expected`.
+fn provided_type(
+    import: &ImportModule,
+    name: &str,
+    expected: &ExternalType,
+) -> Option<ExternalType> {
+    match expected {
+        ExternalType::Func(_) => {
+            let (args, returns) = import.get_func(name).ok()?.func_type().ok()?;
+            Some(ExternalType::Func(wasmedge_types::FuncType::new(
+                Some(args),
+                Some(returns),
+            )))
+        }
+        ExternalType::Table(_) => {
+            let (elem_ty, min, max) = import.get_table(name).ok()?.get_type().ok()?;
+            Some(ExternalType::Table(TableType::new(elem_ty, min, max)))
+        }
+        ExternalType::Memory(_) => {
+            let (min, max, shared) = import.get_memory(name).ok()?.get_type().ok()?;
+            Some(ExternalType::Memory(
+                MemoryType::new(min, max, shared).ok()?,
+            ))
+        }
+        ExternalType::Global(_) => {
+            let (val_ty, mutability) = import.get_global(name).ok()?.get_type().ok()?;
+            Some(ExternalType::Global(GlobalType::new(val_ty, mutability)))
+        }
+    }
+}
+
+impl Linker {
+    /// Checks every import of `module` against the registered import modules.
+    ///
+    /// Unlike instantiation, which stops at the first unresolved import, this reports all of them.
+    pub fn check_imports(&self, module: &AstModule) -> Result<(), LinkReport> {
+        let mut report = LinkReport::default();
+        for import in module.imports() {
+            let import_obj = match self.executor.import_module(&import.module) {
+                Some(import_obj) => import_obj,
+                None => {
+                    report.issues.push(LinkIssue::UnknownModule {
+                        module: import.module,
+                        name: import.name,
+                        expected: import.ty,
+                    });
+                    continue;
+                }
+            };
+
+            match provided_type(import_obj, &import.name, &import.ty) {
+                None => report.issues.push(LinkIssue::UnknownImport {
+                    module: import.module,
+                    name: import.name,
+                    expected: import.ty,
+                }),
+                Some(provided) if !types_match(&import.ty, &provided) => {
+                    report.issues.push(LinkIssue::IncompatibleImportType {
+                        module: import.module,
+                        name: import.name,
+                        expected: import.ty,
+                        provided,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        match report.is_ok() {
+            true => Ok(()),
+            false => Err(report),
+        }
+    }
+}
+
+impl AsyncLinker {
+    /// Checks every import of `module` against the registered import modules, see [Linker::check_imports].
+    pub fn check_imports(&self, module: &AstModule) -> Result<(), LinkReport> {
+        self.real_linker.check_imports(module)
+    }
+}
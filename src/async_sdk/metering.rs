@@ -0,0 +1,255 @@
+//! Defines gas metering: a per-instruction cost table and a cost limit applied to each call.
+//!
+//! Costs are only collected when the [Config](super::config::Config) turns on `measure_cost`.
+//!
+//! An [AsyncLinker] call is metered as a whole, across all the polls it takes. The cost of replaying
+//! the asyncify instrumentation while rewinding into a suspended call, and of the asyncify control
+//! exports, is refunded, so a call costs the same whether or not it suspends. The cost limit is lifted
+//! while they run, so a call close to its limit is not stopped by work it is not charged for.
+
+use std::fmt;
+
+use wasmedge_sys::ffi;
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::async_mod::AsyncLinker;
+use super::error::{Error, Operation};
+use super::statistics::Counters;
+use super::Linker;
+
+/// The WasmEdge result code of `CostLimitExceeded`.
+pub(crate) const COST_LIMIT_EXCEEDED: u32 = 0x03;
+
+/// The error source of a call that ran out of gas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfGas {
+    /// The cost limit of the call.
+    pub limit: u64,
+    /// The cost charged to the call when it was stopped.
+    pub used: u64,
+}
+
+impl fmt::Display for OutOfGas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "out of gas: used {} of {}", self.used, self.limit)
+    }
+}
+
+impl std::error::Error for OutOfGas {}
+
+#[derive(Debug, Default)]
+pub(crate) struct GasState {
+    limit: Option<u64>,
+    /// The total cost when the running call started.
+    call_start: u64,
+    /// The cost refunded to the running call.
+    refunded: u64,
+    /// The counters when the running refunded work, e.g. a rewind, started.
+    refund_start: Option<Counters>,
+}
+
+impl Linker {
+    /// Sets the cost of each instruction, indexed by opcode.
+    pub fn set_cost_table(&mut self, table: &[u64]) {
+        let mut table = table.to_vec();
+        unsafe {
+            ffi::WasmEdge_StatisticsSetCostTable(
+                self.executor.inner_stat.0,
+                table.as_mut_ptr(),
+                table.len() as u32,
+            )
+        }
+    }
+
+    /// Sets the cost limit of each call; `None` removes it.
+    ///
+    /// A call that exceeds the limit fails with an [Error] whose source is [OutOfGas].
+    ///
+    /// # Error
+    ///
+    /// If the [Config](super::config::Config) of this linker does not turn on `measure_cost`, no cost
+    /// is collected to hold a call to, and setting a limit returns an error.
+    pub fn set_cost_limit(&mut self, limit: Option<u64>) -> WasmEdgeResult<()> {
+        if limit.is_some() && !self.measure_cost {
+            return Err(WasmEdgeError::Operation(
+                "a cost limit requires the measure_cost option".to_string(),
+            ));
+        }
+        self.gas.limit = limit;
+        self.apply_cost_limit();
+        Ok(())
+    }
+
+    pub fn cost_limit(&self) -> Option<u64> {
+        self.gas.limit
+    }
+
+    /// Returns the cost charged to the running or the last call.
+    pub fn call_cost(&self) -> u64 {
+        self.executor
+            .counters()
+            .total_cost
+            .saturating_sub(self.gas.call_start)
+            .saturating_sub(self.gas.refunded)
+    }
+
+    fn apply_cost_limit(&mut self) {
+        let limit = match self.gas.limit {
+            Some(_) if self.gas.refund_start.is_some() => u64::MAX,
+            Some(limit) => self
+                .gas
+                .call_start
+                .saturating_add(self.gas.refunded)
+                .saturating_add(limit),
+            None => u64::MAX,
+        };
+        unsafe { ffi::WasmEdge_StatisticsSetCostLimit(self.executor.inner_stat.0, limit) }
+    }
+
+    /// Starts metering a new call, unless it is re-entrant.
+    pub(crate) fn begin_metered_call(&mut self) {
+        if self.call_stack.is_empty() {
+            self.gas.call_start = self.executor.counters().total_cost;
+            self.gas.refunded = 0;
+            self.gas.refund_start = None;
+            self.apply_cost_limit();
+        }
+    }
+
+    /// Starts work whose cost is refunded, lifting the cost limit until
+    /// [end_refund](Linker::end_refund). Returns `false` if refunded work is already running, which
+    /// then covers this work too, so nothing is refunded twice.
+    pub(crate) fn begin_refund(&mut self) -> bool {
+        if self.gas.refund_start.is_some() {
+            return false;
+        }
+        self.gas.refund_start = Some(self.executor.counters());
+        self.apply_cost_limit();
+        true
+    }
+
+    /// Refunds the cost and the instructions of the running refunded work, and puts the cost limit
+    /// back.
+    pub(crate) fn end_refund(&mut self) {
+        if let Some(start) = self.gas.refund_start.take() {
+            let refund = self.executor.counters().since(start);
+            self.gas.refunded += refund.total_cost;
+            self.executor.refund_since(start);
+            self.apply_cost_limit();
+        }
+    }
+
+    pub(crate) fn out_of_gas(&self, name: &str) -> Error {
+        let used = self.call_cost();
+        Error::new(
+            Operation::Call,
+            OutOfGas {
+                limit: self.gas.limit.unwrap_or(used),
+                used,
+            },
+        )
+        .with_name(name)
+        .with_code(COST_LIMIT_EXCEEDED)
+    }
+}
+
+impl AsyncLinker {
+    /// Sets the cost of each instruction, indexed by opcode, see [Linker::set_cost_table].
+    pub fn set_cost_table(&mut self, table: &[u64]) {
+        self.real_linker.set_cost_table(table)
+    }
+
+    /// Sets the cost limit of each call, see [Linker::set_cost_limit].
+    pub fn set_cost_limit(&mut self, limit: Option<u64>) -> WasmEdgeResult<()> {
+        self.real_linker.set_cost_limit(limit)
+    }
+
+    pub fn cost_limit(&self) -> Option<u64> {
+        self.real_linker.cost_limit()
+    }
+
+    pub fn call_cost(&self) -> u64 {
+        self.real_linker.call_cost()
+    }
+
+    /// Marks the start of a rewind, whose replayed instrumentation is refunded by
+    /// [end_replay](AsyncLinker::end_replay). The cost limit is lifted before the rewind starts.
+    pub(crate) fn begin_replay(&mut self) {
+        self.real_linker.begin_refund();
+    }
+
+    /// Refunds the cost of the rewind once it reached the suspended host function, and puts the cost
+    /// limit back.
+    pub(crate) fn end_replay(&mut self) {
+        self.real_linker.end_refund();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::super::ast_module::Loader;
+    use super::super::config::Config;
+    use super::super::types::WasmVal;
+    use super::super::AsLinker;
+    use super::*;
+
+    const SPIN: &str = r#"(module
+        (func (export "spin") (param $n i32)
+            (loop $l
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (br_if $l (local.get $n)))))"#;
+
+    fn linker(measure_cost: bool) -> Box<Linker> {
+        let mut config = Config::create().unwrap();
+        config.measure_cost(measure_cost);
+        let config = Some(config);
+        let module = Loader::create(&config)
+            .unwrap()
+            .load_from_wat(SPIN)
+            .unwrap();
+        let mut linker = Linker::new(&config).unwrap();
+        linker.active_module(&module).unwrap();
+        linker
+    }
+
+    #[test]
+    fn cost_limit_requires_measure_cost() {
+        let mut linker = linker(false);
+        assert!(linker.set_cost_limit(Some(100)).is_err());
+        assert_eq!(linker.cost_limit(), None);
+        assert!(linker.set_cost_limit(None).is_ok());
+    }
+
+    #[test]
+    fn call_over_the_limit_runs_out_of_gas() {
+        let mut linker = linker(true);
+        linker.set_cost_limit(Some(100)).unwrap();
+
+        linker.run("spin", &[WasmVal::I32(2)]).unwrap();
+        assert!(linker.call_cost() > 0);
+
+        let err = linker.run("spin", &[WasmVal::I32(1000)]).unwrap_err();
+        assert_eq!(err.code(), Some(COST_LIMIT_EXCEEDED));
+        let out_of_gas = err.source().unwrap().downcast_ref::<OutOfGas>().unwrap();
+        assert_eq!(out_of_gas.limit, 100);
+
+        // the limit applies to each call on its own
+        linker.run("spin", &[WasmVal::I32(2)]).unwrap();
+    }
+
+    #[test]
+    fn refunded_work_is_not_charged() {
+        let mut linker = linker(true);
+        linker.set_cost_limit(Some(100)).unwrap();
+        linker.begin_metered_call();
+        assert!(linker.begin_refund());
+        // nested refunded work is covered by the outer one
+        assert!(!linker.begin_refund());
+        linker.run_in_call("spin", &[WasmVal::I32(1000)]).unwrap();
+        linker.end_refund();
+        assert_eq!(linker.call_cost(), 0);
+    }
+}
@@ -1,86 +1,287 @@
-use self::{config::Config, executor::Executor, types::WasmVal};
+use self::{
+    config::Config,
+    error::Context,
+    executor::Executor,
+    interrupt::INTERRUPTED,
+    limits::{LimitState, ResourceLimits},
+    metering::{GasState, COST_LIMIT_EXCEEDED},
+    snapshot::STATE_EXPORT_PREFIX,
+    trap::{is_trap, FuncNames},
+    types::WasmVal,
+    wasm_ptr::WasmArgs,
+};
 
 pub mod ast_module;
 pub mod config;
+pub mod config_spec;
+pub mod error;
 pub mod executor;
+pub mod fuel;
+pub mod green_thread;
+pub mod guest_alloc;
+pub mod guest_memory;
 pub mod instance;
+pub mod interrupt;
+pub mod limits;
+pub mod link_check;
+pub mod metering;
 pub mod module;
+pub mod replay;
+pub mod reset;
+pub mod snapshot;
+pub mod statistics;
+pub mod trap;
 pub mod types;
 pub(crate) mod utils;
+pub mod validator;
+pub mod wasm_ptr;
 
 pub use ast_module::*;
+pub use config::Proposal;
+pub use config_spec::ConfigSpec;
+pub use error::{Error, Operation};
+pub use guest_memory::{GuestMemory, Pod};
+pub use instance::global::{GlobType, Global};
+pub use instance::memory::{MemType, Memory};
+pub use instance::table::{Table, TableType};
+pub use interrupt::{InterruptHandle, Timeout};
+pub use limits::{MemoryGrowthHook, ResourceLimits};
+pub use link_check::{LinkIssue, LinkReport};
+pub use metering::OutOfGas;
 pub use module::*;
-use wasmedge_types::error;
-use wasmedge_types::{error::WasmEdgeError, ValType, WasmEdgeResult};
+pub use replay::HostCall;
+pub use snapshot::Snapshot;
+pub use statistics::Statistics;
+pub use trap::{Frame, TrapReport};
+pub use validator::{ValidationReport, Validator};
+pub use wasm_ptr::{WasmPtr, WasmSlice, WasmStr};
+use wasmedge_types::error::{GlobalError, InstanceError};
+use wasmedge_types::{error::WasmEdgeError, Mutability, ValType, WasmEdgeResult};
 
 pub struct Linker {
     pub(crate) inst: Option<module::Instance>,
     pub(crate) executor: Executor,
+    pub(crate) mutable_globals: bool,
+    pub(crate) threads: bool,
+    /// Whether the config turns on `measure_cost`, which a cost limit needs.
+    pub(crate) measure_cost: bool,
+    pub(crate) limit_state: LimitState,
+    pub(crate) func_names: FuncNames,
+    /// The fingerprint of the active module.
+    pub(crate) fingerprint: u64,
+    /// The exports entered from the host that are still running, outermost first.
+    pub(crate) call_stack: Vec<String>,
+    /// The shadow stack of traced guest functions that are still running, outermost first, see
+    /// [trap].
+    pub(crate) guest_stack: Vec<u32>,
+    pub(crate) gas: GasState,
+    /// The active module, kept to re-instantiate it on [reset](Linker::reset).
+    pub(crate) module: Option<AstModule>,
 }
 
 impl Linker {
     pub fn new(config: &Option<Config>) -> WasmEdgeResult<Box<Self>> {
+        Self::new_with_limits(config, ResourceLimits::default())
+    }
+
+    /// Creates a linker whose guest is held to `limits`.
+    ///
+    /// `max_memory_pages` is applied on top of the maximum page count of `config`.
+    pub fn new_with_limits(
+        config: &Option<Config>,
+        limits: ResourceLimits,
+    ) -> WasmEdgeResult<Box<Self>> {
+        let executor = match limits.max_memory_pages {
+            Some(max_pages) => {
+                let mut limited = match config {
+                    Some(config) => Config::copy_from(config)?,
+                    None => Config::create()?,
+                };
+                limited.set_max_memory_pages(max_pages.min(limited.get_max_memory_pages()));
+                Executor::create(&Some(limited))?
+            }
+            None => Executor::create(config)?,
+        };
         let mut linker = Box::new(Linker {
-            executor: Executor::create(config)?,
+            executor,
             inst: None,
+            mutable_globals: config
+                .as_ref()
+                .map_or(true, |config| config.mutable_globals_enabled()),
+            threads: config
+                .as_ref()
+                .map_or(false, |config| config.threads_enabled()),
+            measure_cost: config
+                .as_ref()
+                .map_or(false, |config| config.is_cost_measuring()),
+            limit_state: LimitState {
+                limits,
+                ..Default::default()
+            },
+            func_names: FuncNames::default(),
+            fingerprint: 0,
+            call_stack: vec![],
+            guest_stack: vec![],
+            gas: GasState::default(),
+            module: None,
         });
         if let Some(config) = config {
             if config.wasi_enabled() {
                 let wasi_import_obj = ImportModule::create_wasi(&[], &[], &[])?;
-                linker.executor.register_import_object(wasi_import_obj)?;
+                linker.register_import(wasi_import_obj)?;
             }
         }
 
         Ok(linker)
     }
 
-    pub fn get_memory_slice<'a>(
-        &'a self,
-        name: &str,
-        offset: usize,
-        len: usize,
-    ) -> WasmEdgeResult<&'a [u8]> {
-        let mem = if let Some(inst) = &self.inst {
-            inst.get_memory(name)
+    pub(crate) fn register_import(&mut self, import: ImportModule) -> WasmEdgeResult<()> {
+        self.add_instance()?;
+        self.executor.register_import_object(import)
+    }
+
+    pub fn get_global(&self, name: &str) -> WasmEdgeResult<Global> {
+        if let Some(inst) = &self.inst {
+            inst.get_global(name)
         } else {
-            Err(WasmEdgeError::Instance(error::InstanceError::NotFoundMem(
+            Err(WasmEdgeError::Instance(InstanceError::NotFoundGlobal(
                 name.to_string(),
             )))
-        }?;
-        unsafe {
-            let p = mem.data_pointer_raw(offset, len)?;
-            Ok(std::slice::from_raw_parts(p, len))
         }
     }
 
-    pub fn mut_memory_slice<'a>(
-        &'a mut self,
-        name: &str,
-        offset: usize,
-        len: usize,
-    ) -> WasmEdgeResult<&'a mut [u8]> {
-        let mut mem = if let Some(inst) = &self.inst {
-            inst.get_memory(name)
+    /// Returns the names of the exported globals, without the exports added for
+    /// [snapshots](crate::ast_module::Loader::snapshots).
+    pub fn global_names(&self) -> Option<Vec<String>> {
+        self.inst
+            .as_ref()
+            .and_then(|inst| inst.global_names())
+            .map(|names| {
+                names
+                    .into_iter()
+                    .filter(|name| !name.starts_with(STATE_EXPORT_PREFIX))
+                    .collect()
+            })
+    }
+
+    pub fn get_table(&self, name: &str) -> WasmEdgeResult<Table> {
+        if let Some(inst) = &self.inst {
+            inst.get_table(name)
         } else {
-            Err(WasmEdgeError::Instance(error::InstanceError::NotFoundMem(
+            Err(WasmEdgeError::Instance(InstanceError::NotFoundTable(
                 name.to_string(),
             )))
-        }?;
-        unsafe {
-            let p = mem.data_pointer_mut_raw(offset, len)?;
-            Ok(std::slice::from_raw_parts_mut(p, len))
         }
     }
 
-    pub fn run(&mut self, name: &str, args: &[WasmVal]) -> WasmEdgeResult<Vec<WasmVal>> {
+    pub fn table_names(&self) -> Option<Vec<String>> {
+        self.inst.as_ref().and_then(|inst| inst.table_names())
+    }
+
+    /// Sets the exported mutable global `name`.
+    ///
+    /// # Error
+    ///
+    /// If the global is immutable, `val` does not match its type, or the `ImportExportMutGlobals`
+    /// option is turned off, then an error is returned.
+    pub fn set_global<T: Into<WasmVal>>(&mut self, name: &str, val: T) -> WasmEdgeResult<()> {
+        if !self.mutable_globals {
+            return Err(WasmEdgeError::Global(GlobalError::ModifyConst));
+        }
+        self.get_global(name)?.set(val)
+    }
+
+    pub fn run(&mut self, name: &str, args: &[WasmVal]) -> error::Result<Vec<WasmVal>> {
+        self.begin_call();
+        let r = self
+            .run_in_call(name, args)
+            .and_then(|v| self.report_call_growth(name).map(|_| v));
+        self.end_call();
+        r
+    }
+
+    /// Starts the metering and the deadline of a call, unless it is re-entrant.
+    pub(crate) fn begin_call(&mut self) {
+        if self.call_stack.is_empty() {
+            self.guest_stack.clear();
+            self.executor.begin_call_stats();
+            self.begin_metered_call();
+            self.executor.arm_interrupt();
+        }
+    }
+
+    pub(crate) fn end_call(&mut self) {
+        if self.call_stack.is_empty() {
+            self.executor.end_call_stats();
+            self.executor.disarm_interrupt();
+        }
+    }
+
+    /// Runs `name` as part of the running metered call, see [set_cost_limit](Linker::set_cost_limit).
+    pub(crate) fn run_in_call(
+        &mut self,
+        name: &str,
+        args: &[WasmVal],
+    ) -> error::Result<Vec<WasmVal>> {
         let f = if let Some(inst) = &self.inst {
             inst.get_func(name)
         } else {
-            Err(WasmEdgeError::Instance(error::InstanceError::NotFoundFunc(
+            Err(WasmEdgeError::Instance(InstanceError::NotFoundFunc(
                 name.to_string(),
             )))
-        }?;
-        f.call(&mut self.executor, args)
+        }
+        .context(Operation::Call)
+        .map_err(|e| e.with_name(name))?;
+        self.call_stack.push(name.to_string());
+        let depth = self.guest_stack.len();
+        let r = f.call(&mut self.executor, args).map_err(|e| {
+            let e = e.with_name(name);
+            match e.code() {
+                Some(code) if is_trap(code) => e.with_trap(self.trap_report()),
+                Some(COST_LIMIT_EXCEEDED) => self.out_of_gas(name),
+                Some(INTERRUPTED) => self.timed_out(name, e),
+                _ => e,
+            }
+        });
+        self.call_stack.pop();
+        // the frames a failed call did not leave
+        self.guest_stack.truncate(depth);
+        r
+    }
+}
+
+impl Linker {
+    /// Adds a host-defined global to `import_obj` if the proposals of this linker allow it, see
+    /// [ImportModuleBuilder::add_global].
+    pub(crate) fn add_host_global(
+        &self,
+        import_obj: &mut ImportModule,
+        name: &str,
+        val_ty: ValType,
+        mutability: Mutability,
+        val: WasmVal,
+    ) -> WasmEdgeResult<()> {
+        if mutability == Mutability::Var && !self.mutable_globals {
+            return Err(WasmEdgeError::Global(GlobalError::ModifyConst));
+        }
+        import_obj.add_global(name, val_ty, mutability, val)
+    }
+
+    /// Adds a host-provided memory to `import_obj` if the proposals of this linker allow it, see
+    /// [ImportModuleBuilder::add_memory].
+    pub(crate) fn add_host_memory(
+        &self,
+        import_obj: &mut ImportModule,
+        name: &str,
+        memory: Memory,
+    ) -> WasmEdgeResult<()> {
+        if memory.get_type()?.2 && !self.threads {
+            return Err(WasmEdgeError::Operation(format!(
+                "shared memory {} requires the threads proposal",
+                name
+            )));
+        }
+        import_obj.add_memory(name, memory)
     }
 }
 
@@ -100,6 +301,45 @@ impl ImportModuleBuilder<'_> {
         self.import_obj
             .add_func(name, self.linker_ctx, ty, real_fn, cost)
     }
+
+    /// Adds a host-defined global, see [ImportModule::add_global].
+    ///
+    /// Mutable globals are only accepted if the `ImportExportMutGlobals` option is turned on.
+    pub fn add_global(
+        &mut self,
+        name: &str,
+        val_ty: ValType,
+        mutability: Mutability,
+        val: WasmVal,
+    ) -> WasmEdgeResult<()> {
+        self.linker_ctx
+            .add_host_global(&mut self.import_obj, name, val_ty, mutability, val)
+    }
+
+    /// Adds a host-defined table, see [ImportModule::add_table].
+    pub fn add_table(&mut self, name: &str, table: Table) -> WasmEdgeResult<()> {
+        self.import_obj.add_table(name, table)
+    }
+
+    /// Adds a host-provided memory, see [ImportModule::add_memory].
+    ///
+    /// Shared memories are only accepted if the `Threads` option is turned on.
+    pub fn add_memory(&mut self, name: &str, memory: Memory) -> WasmEdgeResult<()> {
+        self.linker_ctx
+            .add_host_memory(&mut self.import_obj, name, memory)
+    }
+
+    /// Adds a host function taking typed arguments such as [WasmPtr], [WasmSlice] and [WasmStr].
+    pub fn add_typed_func<A: WasmArgs>(
+        &mut self,
+        name: &str,
+        returns: Vec<ValType>,
+        real_fn: fn(Option<&mut Linker>, A) -> Result<Vec<WasmVal>, u32>,
+        cost: u64,
+    ) -> WasmEdgeResult<()> {
+        self.import_obj
+            .add_typed_func(name, self.linker_ctx, returns, real_fn, cost)
+    }
 }
 
 pub trait AsLinker {
@@ -109,7 +349,7 @@ pub trait AsLinker {
         f: &mut F,
     ) -> Result<(), WasmEdgeError>;
 
-    fn active_module(&mut self, ast_module: &AstModule) -> Result<(), WasmEdgeError>;
+    fn active_module(&mut self, ast_module: &AstModule) -> error::Result<()>;
 }
 
 impl AsLinker for Box<Linker> {
@@ -129,15 +369,26 @@ impl AsLinker for Box<Linker> {
             linker_ctx,
         } = builder;
 
-        linker_ctx.executor.register_import_object(import_obj)?;
+        linker_ctx.register_import(import_obj)?;
 
         Ok(())
     }
 
-    fn active_module(&mut self, module: &AstModule) -> Result<(), WasmEdgeError> {
+    fn active_module(&mut self, module: &AstModule) -> error::Result<()> {
+        self.register_interrupt_check(module)
+            .map_err(|e| Error::new(Operation::Link, e))?;
+        self.register_call_trace(module)
+            .map_err(|e| Error::new(Operation::Link, e))?;
+        self.check_imports(module)
+            .map_err(|report| Error::new(Operation::Link, report))?;
+        self.check_tables(module).context(Operation::Instantiate)?;
+        self.add_instance().context(Operation::Instantiate)?;
         let inst = self.executor.register_active_module(module)?;
         self.inst = Some(inst);
-        Ok(())
+        self.func_names = module.func_names.clone();
+        self.fingerprint = module.fingerprint;
+        self.module = Some(module.clone());
+        self.track_memories().context(Operation::Instantiate)
     }
 }
 
@@ -154,58 +405,184 @@ pub mod async_mod {
     use wasmedge_sys::ffi;
     use wasmedge_types::{
         error::{FuncError, WasmEdgeError},
-        ValType, WasmEdgeResult,
+        Mutability, ValType, WasmEdgeResult,
     };
 
     use super::{
         config::Config,
-        instance::function::{FuncType, Function, InnerFunc},
+        error::{self, Error, Operation},
+        green_thread::{TaskFutures, TaskTable},
+        instance::function::{typed_args, FuncType, Function, InnerFunc},
+        instance::global::Global,
+        instance::memory::Memory,
+        instance::table::Table,
+        interrupt::INTERRUPTED,
+        limits::ResourceLimits,
+        replay::{raw_args, record_host_call, HostCallLog, HostFunc, HostFuncs},
         types::{WasmEdgeString, WasmVal},
+        wasm_ptr::WasmArgs,
         AsLinker, AstModule, ImportModule, Linker,
     };
 
-    type AsyncFn<'a> = fn(&'a mut AsyncLinker, Vec<WasmVal>) -> ResultFuture<'a>;
+    const ASYNCIFY_UNWINDING: i32 = 1;
+    const ASYNCIFY_REWINDING: i32 = 2;
+
+    type AsyncFn<'a, A = Vec<WasmVal>> = fn(&'a mut AsyncLinker, A) -> ResultFuture<'a>;
 
     pub type ResultFuture<'a> = Box<dyn Future<Output = WasmEdgeResult<Vec<WasmVal>>> + 'a>;
 
+    /// The future of a guest call made through [AsyncLinker::call].
+    ///
+    /// A call made from inside a host future while an outer call is running is re-entrant: the outer
+    /// asyncify stack and pending host futures are set aside while the inner call runs, and the inner
+    /// ones are kept in this future while the inner call is suspended, so dropping it drops them.
+    /// Dropping a suspended top-level call ends it, as if it had failed.
     pub struct WasmEdgeResultFuture<'a> {
         linker: &'a mut AsyncLinker,
         name: String,
         args: Vec<WasmVal>,
+        stack: Option<Vec<u8>>,
+        futures: TaskFutures,
+        /// Whether the call was polled, which starts metering it.
+        started: bool,
+        /// Whether the call was first polled from inside an outer call.
+        nested: bool,
+        finished: bool,
+    }
+
+    impl<'a> WasmEdgeResultFuture<'a> {
+        pub(crate) fn new(linker: &'a mut AsyncLinker, name: &str, args: Vec<WasmVal>) -> Self {
+            WasmEdgeResultFuture {
+                linker,
+                name: name.to_string(),
+                args,
+                stack: None,
+                futures: TaskFutures::new(),
+                started: false,
+                nested: false,
+                finished: false,
+            }
+        }
+
+        fn poll_nested(&mut self) -> error::Result<Poll<Vec<WasmVal>>> {
+            let linker = &mut *self.linker;
+            let outer_state = linker.asyncify_state();
+            let outer_stack = linker.asyncify_stack()?;
+            let mut outer_futures =
+                std::mem::replace(linker.task_futures(), std::mem::take(&mut self.futures));
+
+            let r = match self.stack.take() {
+                Some(stack) => linker
+                    .set_asyncify_stack(&stack)
+                    .and_then(|_| linker.asyncify_replay()),
+                None => linker.asyncify_normal(),
+            }
+            .and_then(|_| {
+                linker.call_depth += 1;
+                let r = linker.real_call(&self.name, &self.args);
+                linker.call_depth -= 1;
+                r
+            })
+            .and_then(|v| match linker.asyncify_done() {
+                true => Ok(Poll::Ready(v)),
+                false => {
+                    self.stack = Some(linker.asyncify_stack()?);
+                    Ok(Poll::Pending)
+                }
+            });
+
+            std::mem::swap(linker.task_futures(), &mut outer_futures);
+            self.futures = outer_futures;
+            if !matches!(r, Ok(Poll::Pending)) {
+                self.futures.clear();
+            }
+            let restored =
+                linker
+                    .set_asyncify_stack(&outer_stack)
+                    .and_then(|_| match outer_state {
+                        ASYNCIFY_UNWINDING => linker.asyncify_interrupt(),
+                        ASYNCIFY_REWINDING => linker.asyncify_rewind(),
+                        _ => linker.asyncify_normal(),
+                    });
+            r.and_then(|p| restored.map(|_| p))
+        }
+    }
+
+    impl Drop for WasmEdgeResultFuture<'_> {
+        fn drop(&mut self) {
+            if self.started && !self.nested && !self.finished && self.linker.suspended.is_some() {
+                let _ = self.linker.finish_call();
+            }
+        }
     }
 
     impl Future for WasmEdgeResultFuture<'_> {
-        type Output = WasmEdgeResult<Vec<WasmVal>>;
+        type Output = error::Result<Vec<WasmVal>>;
 
         fn poll(
             self: std::pin::Pin<&mut Self>,
             cx: &mut Context<'_>,
         ) -> std::task::Poll<Self::Output> {
-            let WasmEdgeResultFuture { linker, name, args } = self.get_mut();
+            let this = self.get_mut();
+            if !this.started {
+                this.started = true;
+                this.nested = this.linker.call_depth > 0;
+                if !this.nested {
+                    this.linker.real_linker.begin_call();
+                }
+            }
+            if this.nested {
+                let r = match this.poll_nested() {
+                    Ok(Poll::Ready(v)) => Poll::Ready(Ok(v)),
+                    Ok(Poll::Pending) => Poll::Pending,
+                    Err(e) => Poll::Ready(Err(e)),
+                };
+                this.finished = r.is_ready();
+                return r;
+            }
+
+            let WasmEdgeResultFuture {
+                linker,
+                name,
+                args,
+                finished,
+                ..
+            } = this;
             linker.cx = cx.waker().clone();
 
-            linker.asyncify_resume();
-            let r = linker.real_call(name, args);
-            let r = if r.is_err() {
-                Poll::Ready(r)
-            } else {
-                if linker.asyncify_done() {
-                    Poll::Ready(r)
-                } else {
+            let r = linker
+                .real_linker
+                .check_interrupt(name)
+                .and_then(|_| linker.asyncify_resume())
+                .and_then(|_| {
+                    linker.call_depth += 1;
+                    let r = linker.real_call(name, args);
+                    linker.call_depth -= 1;
+                    r
+                })
+                .and_then(|v| linker.real_linker.report_call_growth(name).map(|_| v));
+            match r {
+                Ok(_) if !linker.asyncify_done() => {
+                    linker.suspended = Some((name.clone(), args.clone()));
+                    // the outer call is unwound, give the spawned guest tasks a turn
+                    if let Err(e) = linker.run_green_tasks() {
+                        *finished = true;
+                        return Poll::Ready(linker.finish_call().and(Err(e)));
+                    }
                     Poll::Pending
                 }
-            };
-            if r.is_ready() {
-                linker.asyncify_normal();
+                r => {
+                    *finished = true;
+                    Poll::Ready(linker.finish_call().and(r))
+                }
             }
-            r
         }
     }
 
-    extern "C" fn wrapper_async_fn(
+    extern "C" fn wrapper_async_fn<A: WasmArgs>(
         key_ptr: *mut c_void,
         data_ptr: *mut c_void,
-        _mem_ctx: *mut ffi::WasmEdge_MemoryInstanceContext,
+        mem_ctx: *mut ffi::WasmEdge_MemoryInstanceContext,
         params: *const ffi::WasmEdge_Value,
         param_len: u32,
         returns: *mut ffi::WasmEdge_Value,
@@ -219,22 +596,45 @@ pub mod async_mod {
             let mut cx = Context::from_waker(&cx);
             let fut_is_ready;
             let r = {
-                let mut fut = if data.asyncify_done() {
-                    let real_fn: fn(&mut AsyncLinker, Vec<WasmVal>) -> ResultFuture =
-                        unsafe { std::mem::transmute(key_ptr) };
-
-                    let input = {
-                        let raw_input =
-                            unsafe { std::slice::from_raw_parts(params, param_len as usize) };
-                        raw_input
-                            .iter()
-                            .map(|r| (*r).into())
-                            .collect::<Vec<WasmVal>>()
+                let rewinding = !data.asyncify_done();
+                if rewinding {
+                    data.end_replay();
+                } else if data.real_linker.executor.poll_interrupt() {
+                    return ffi::WasmEdge_Result {
+                        Code: INTERRUPTED as u8,
                     };
-
-                    Pin::from(real_fn(data, input))
+                }
+                let stored = match rewinding {
+                    true => data.func_futures().pop_back(),
+                    false => None,
+                };
+                // a call restored from a snapshot is rewound without its host future, so it is
+                // created again
+                let mut fut = if let Some(fut) = stored {
+                    fut
                 } else {
-                    data.func_futures().pop_back().unwrap()
+                    let func = key_ptr as *const HostFunc;
+                    let real_fn: fn(&mut AsyncLinker, A) -> ResultFuture =
+                        unsafe { std::mem::transmute((*func).real_fn) };
+
+                    let input = match typed_args::<A>(mem_ctx, params, param_len) {
+                        Ok(input) => input,
+                        Err(c) => return ffi::WasmEdge_Result { Code: c },
+                    };
+
+                    if data.logs_host_calls() {
+                        let name = unsafe { &(*func).name };
+                        let args = raw_args(params, param_len);
+                        match data.replayed_host_call(name, &args) {
+                            Some(fut) => fut,
+                            None => {
+                                let recording = data.host_call_recording(name, &args);
+                                record_host_call(Pin::from(real_fn(data, input)), recording)
+                            }
+                        }
+                    } else {
+                        Pin::from(real_fn(data, input))
+                    }
                 };
 
                 let return_len = return_len as usize;
@@ -262,12 +662,20 @@ pub mod async_mod {
                 }
             };
 
-            if fut_is_ready {
-                data.asyncify_normal();
+            let state = if fut_is_ready {
+                data.asyncify_normal()
             } else {
-                data.asyncify_interrupt();
+                data.asyncify_interrupt()
             };
-            r
+            match state {
+                Ok(_) => r,
+                Err(e) => ffi::WasmEdge_Result {
+                    Code: e
+                        .code()
+                        .and_then(|code| u8::try_from(code).ok())
+                        .unwrap_or(0x02),
+                },
+            }
         } else {
             ffi::WasmEdge_Result { Code: 0 }
         }
@@ -282,7 +690,7 @@ pub mod async_mod {
             f: F,
         ) -> Result<(), WasmEdgeError>;
 
-        fn active_module(&mut self, ast_module: &AstModule) -> Result<(), WasmEdgeError>;
+        fn active_module(&mut self, ast_module: &AstModule) -> error::Result<()>;
 
         fn call(&mut self, name: &str, args: Vec<WasmVal>) -> WasmEdgeResultFuture;
     }
@@ -305,33 +713,36 @@ pub mod async_mod {
                 import_obj,
                 linker_ctx,
             } = builder;
-            linker_ctx
-                .real_linker
-                .executor
-                .register_import_object(import_obj)?;
+            linker_ctx.real_linker.register_import(import_obj)?;
             Ok(())
         }
 
-        fn active_module(&mut self, module: &AstModule) -> Result<(), WasmEdgeError> {
+        fn active_module(&mut self, module: &AstModule) -> error::Result<()> {
             let linker_ctx = unsafe { self.as_mut().get_unchecked_mut() };
+            linker_ctx
+                .register_fuel_yield(module)
+                .map_err(|e| Error::new(Operation::Link, e))?;
             linker_ctx.real_linker.active_module(module)
         }
 
         fn call(&mut self, name: &str, args: Vec<WasmVal>) -> WasmEdgeResultFuture {
             let linker_ctx = unsafe { self.as_mut().get_unchecked_mut() };
-            WasmEdgeResultFuture {
-                linker: linker_ctx,
-                name: name.to_string(),
-                args,
-            }
+            WasmEdgeResultFuture::new(linker_ctx, name, args)
         }
     }
 
     pub struct AsyncLinker {
-        cx: Waker,
-        real_linker: Box<Linker>,
+        pub(crate) cx: Waker,
+        pub(crate) real_linker: Box<Linker>,
         // func_futures: std::collections::LinkedList<Pin<ResultFuture<'this>>>,
         func_futures_ptr: NonNull<c_void>,
+        pub(crate) tasks: TaskTable,
+        pub(crate) call_depth: usize,
+        /// The export and arguments of the top-level call suspended between two polls.
+        pub(crate) suspended: Option<(String, Vec<WasmVal>)>,
+        /// The keys the async host functions are bound with, see [HostFunc].
+        pub(crate) host_funcs: HostFuncs,
+        pub(crate) host_call_log: HostCallLog,
         _unpin: PhantomPinned,
     }
 
@@ -353,7 +764,20 @@ pub mod async_mod {
             unsafe { self.func_futures_ptr.cast().as_mut() }
         }
 
+        /// The pending host futures of the running call, used to swap them out on a task switch.
+        pub(crate) fn task_futures(&mut self) -> &mut TaskFutures {
+            unsafe { self.func_futures_ptr.cast().as_mut() }
+        }
+
         pub fn new(config: &Option<Config>) -> WasmEdgeResult<Pin<Box<Self>>> {
+            Self::new_with_limits(config, ResourceLimits::default())
+        }
+
+        /// Creates a linker whose guest is held to `limits`, see [Linker::new_with_limits].
+        pub fn new_with_limits(
+            config: &Option<Config>,
+            limits: ResourceLimits,
+        ) -> WasmEdgeResult<Pin<Box<Self>>> {
             unsafe {
                 let func_futures_ptr = Box::leak(Box::new(std::collections::LinkedList::<
                     Pin<ResultFuture<'static>>,
@@ -361,47 +785,171 @@ pub mod async_mod {
 
                 Ok(Box::pin(AsyncLinker {
                     cx: waker_fn::waker_fn(|| {}),
-                    real_linker: Linker::new(config)?,
+                    real_linker: Linker::new_with_limits(config, limits)?,
                     func_futures_ptr: NonNull::new_unchecked(func_futures_ptr),
+                    tasks: TaskTable::default(),
+                    call_depth: 0,
+                    suspended: None,
+                    host_funcs: HostFuncs::default(),
+                    host_call_log: HostCallLog::default(),
                     _unpin: PhantomPinned,
                 }))
             }
         }
 
+        /// Calls the exported guest function `name`.
+        ///
+        /// This may also be awaited from inside an async host function to call back into the guest
+        /// while the outer call is suspended.
         pub fn call(&mut self, name: &str, args: Vec<WasmVal>) -> WasmEdgeResultFuture {
-            WasmEdgeResultFuture {
-                linker: self,
-                name: name.to_string(),
-                args,
+            WasmEdgeResultFuture::new(self, name, args)
+        }
+
+        /// Ends the top-level call: drops its suspended state, pending host futures and green tasks,
+        /// and clears the asyncify state.
+        pub(crate) fn finish_call(&mut self) -> error::Result<()> {
+            self.suspended = None;
+            self.real_linker.end_call();
+            self.tasks.clear();
+            self.task_futures().clear();
+            self.asyncify_normal()
+        }
+
+        pub(crate) fn real_call(
+            &mut self,
+            name: &str,
+            args: &[WasmVal],
+        ) -> error::Result<Vec<WasmVal>> {
+            self.real_linker.run_in_call(name, args)
+        }
+
+        /// Calls one of the control exports added by the asyncify pass.
+        ///
+        /// Their cost is refunded, as they are not part of the guest's own work, and the cost limit
+        /// is lifted while they run.
+        fn asyncify_call(&mut self, name: &str) -> error::Result<()> {
+            let refunded = self.real_linker.begin_refund();
+            let r = self.real_call(name, &[]).map(|_| ()).map_err(|e| {
+                let code = e.code();
+                let e = Error::new(Operation::Asyncify, e).with_name(name);
+                match code {
+                    Some(code) => e.with_code(code),
+                    None => e,
+                }
+            });
+            if refunded {
+                self.real_linker.end_refund();
             }
+            r
         }
 
-        fn real_call(&mut self, name: &str, args: &[WasmVal]) -> WasmEdgeResult<Vec<WasmVal>> {
-            self.real_linker.run(name, args)
+        pub(crate) fn asyncify_interrupt(&mut self) -> error::Result<()> {
+            self.asyncify_call("asyncify_start_unwind")
         }
 
-        fn asyncify_interrupt(&mut self) {
-            self.real_call("asyncify_start_unwind", &[]).unwrap();
+        pub(crate) fn asyncify_rewind(&mut self) -> error::Result<()> {
+            self.asyncify_call("asyncify_start_rewind")
         }
 
-        fn asyncify_resume(&mut self) {
-            if !self.asyncify_done() {
-                self.real_call("asyncify_start_rewind", &[]).unwrap();
+        pub(crate) fn asyncify_resume(&mut self) -> error::Result<()> {
+            match self.asyncify_done() {
+                true => Ok(()),
+                false => self.asyncify_replay(),
             }
         }
 
-        fn asyncify_normal(&mut self) {
-            self.real_call("asyncify_stop_unwind", &[]).unwrap();
+        /// Starts rewinding into a suspended call, refunding the replayed instrumentation once it
+        /// reaches the suspended host function.
+        pub(crate) fn asyncify_replay(&mut self) -> error::Result<()> {
+            self.begin_replay();
+            self.asyncify_rewind()
+        }
+
+        pub(crate) fn asyncify_normal(&mut self) -> error::Result<()> {
+            self.asyncify_call("asyncify_stop_unwind")
+        }
+
+        pub(crate) fn asyncify_done(&mut self) -> bool {
+            self.asyncify_state() == 0
         }
 
-        fn asyncify_done(&mut self) -> bool {
+        pub(crate) fn asyncify_state(&mut self) -> i32 {
+            let refunded = self.real_linker.begin_refund();
             let r = self.real_call("asyncify_get_state", &[]);
+            if refunded {
+                self.real_linker.end_refund();
+            }
             if let Ok(s) = r {
                 if let Some(WasmVal::I32(i)) = s.first() {
-                    return *i == 0;
+                    return *i;
                 }
             }
-            return true;
+            return 0;
+        }
+
+        pub fn get_global(&self, name: &str) -> WasmEdgeResult<Global> {
+            self.real_linker.get_global(name)
+        }
+
+        pub fn global_names(&self) -> Option<Vec<String>> {
+            self.real_linker.global_names()
+        }
+
+        pub fn get_table(&self, name: &str) -> WasmEdgeResult<Table> {
+            self.real_linker.get_table(name)
+        }
+
+        pub fn table_names(&self) -> Option<Vec<String>> {
+            self.real_linker.table_names()
+        }
+
+        /// Sets the exported mutable global `name`, see [Linker::set_global].
+        pub fn set_global<T: Into<WasmVal>>(&mut self, name: &str, val: T) -> WasmEdgeResult<()> {
+            self.real_linker.set_global(name, val)
+        }
+
+        /// Calls a guest function that never suspends, e.g. an allocator, from inside a host future.
+        ///
+        /// The asyncify state of the outer call is set aside for the duration of the call.
+        pub fn call_sync(&mut self, name: &str, args: &[WasmVal]) -> error::Result<Vec<WasmVal>> {
+            if self.call_depth == 0 {
+                self.real_linker.begin_call();
+            }
+            let outer_state = self.asyncify_state();
+            if outer_state != 0 {
+                self.asyncify_normal()?;
+            }
+
+            self.call_depth += 1;
+            let r = self.real_call(name, args);
+            self.call_depth -= 1;
+
+            let r = match r {
+                Ok(_) if !self.asyncify_done() => {
+                    self.asyncify_normal()?;
+                    Err(Error::new(
+                        Operation::Call,
+                        WasmEdgeError::Operation("suspended inside a synchronous call".to_string()),
+                    )
+                    .with_name(name))
+                }
+                r => r,
+            };
+            let r = match self.call_depth {
+                0 => {
+                    let r = r.and_then(|v| self.real_linker.report_call_growth(name).map(|_| v));
+                    self.real_linker.end_call();
+                    r
+                }
+                _ => r,
+            };
+
+            match outer_state {
+                ASYNCIFY_UNWINDING => self.asyncify_interrupt()?,
+                ASYNCIFY_REWINDING => self.asyncify_rewind()?,
+                _ => {}
+            }
+            r
         }
     }
 
@@ -421,20 +969,64 @@ pub mod async_mod {
             self.import_obj
                 .add_async_func(name, self.linker_ctx, ty, real_fn, cost)
         }
+
+        /// Adds a host-defined global, see [ImportModuleBuilder::add_global](super::ImportModuleBuilder::add_global).
+        pub fn add_global(
+            &mut self,
+            name: &str,
+            val_ty: ValType,
+            mutability: Mutability,
+            val: WasmVal,
+        ) -> WasmEdgeResult<()> {
+            self.linker_ctx.real_linker.add_host_global(
+                &mut self.import_obj,
+                name,
+                val_ty,
+                mutability,
+                val,
+            )
+        }
+
+        /// Adds a host-defined table, see [ImportModule::add_table].
+        pub fn add_table(&mut self, name: &str, table: Table) -> WasmEdgeResult<()> {
+            self.import_obj.add_table(name, table)
+        }
+
+        /// Adds a host-provided memory, see [ImportModuleBuilder::add_memory](super::ImportModuleBuilder::add_memory).
+        pub fn add_memory(&mut self, name: &str, memory: Memory) -> WasmEdgeResult<()> {
+            self.linker_ctx
+                .real_linker
+                .add_host_memory(&mut self.import_obj, name, memory)
+        }
+
+        /// Adds an async host function taking typed arguments such as [WasmPtr](super::WasmPtr),
+        /// [WasmSlice](super::WasmSlice) and [WasmStr](super::WasmStr).
+        pub fn add_typed_func<A: WasmArgs>(
+            &mut self,
+            name: &str,
+            returns: Vec<ValType>,
+            real_fn: AsyncFn<'_, A>,
+            cost: u64,
+        ) -> WasmEdgeResult<()> {
+            let params = A::val_types().unwrap_or_default();
+            self.import_obj
+                .add_async_func(name, self.linker_ctx, (params, returns), real_fn, cost)
+        }
     }
 
     impl ImportModule {
-        pub fn add_async_func(
+        pub fn add_async_func<A: WasmArgs>(
             &mut self,
             name: &str,
             data: &mut AsyncLinker,
             ty: (Vec<ValType>, Vec<ValType>),
-            real_fn: AsyncFn,
+            real_fn: AsyncFn<'_, A>,
             cost: u64,
         ) -> WasmEdgeResult<()> {
             let func_name = WasmEdgeString::new(name);
+            let key = data.host_func_key(real_fn as *const (), format!("{}.{}", self.name(), name));
             unsafe {
-                let func = Function::create_async(ty, real_fn, data, cost)?;
+                let func = Function::create_async::<_, A>(ty, key, data, cost)?;
                 ffi::WasmEdge_ModuleInstanceAddFunction(
                     self.inner.0,
                     func_name.as_raw(),
@@ -446,9 +1038,9 @@ pub mod async_mod {
     }
 
     impl Function {
-        pub(crate) fn create_async<T: Sized>(
+        pub(crate) fn create_async<T: Sized, A: WasmArgs>(
             ty: (Vec<ValType>, Vec<ValType>),
-            real_fn: AsyncFn,
+            key: *const HostFunc,
             data: *mut T,
             cost: u64,
         ) -> WasmEdgeResult<Self> {
@@ -456,8 +1048,8 @@ pub mod async_mod {
                 let ty = FuncType::create(ty.0, ty.1)?;
                 let ctx = ffi::WasmEdge_FunctionInstanceCreateBinding(
                     ty.inner.0,
-                    Some(wrapper_async_fn),
-                    real_fn as *mut c_void,
+                    Some(wrapper_async_fn::<A>),
+                    key as *mut c_void,
                     data.cast(),
                     cost,
                 );
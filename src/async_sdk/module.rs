@@ -3,15 +3,17 @@
 use std::ffi::{c_void, CString};
 use wasmedge_sys::ffi;
 use wasmedge_types::error::{InstanceError, WasmEdgeError};
-use wasmedge_types::{ValType, WasmEdgeResult};
+use wasmedge_types::{Mutability, ValType, WasmEdgeResult};
 
 use super::{
-    instance::{function::FuncRef, memory::Memory},
+    instance::borrowed_error,
+    instance::{function::FuncRef, global::Global, memory::Memory, table::Table},
     instance::{
         function::{Function, InnerFunc},
-        memory::InnerMemory,
+        global::GlobType,
     },
     types::{WasmEdgeString, WasmVal},
+    wasm_ptr::WasmArgs,
 };
 
 trait AsInnerInstance {
@@ -90,6 +92,40 @@ pub(crate) trait AsInstance {
 
     /// Returns the names of all exported [memory instances](crate::Memory) in this module instance.
     fn mem_names(&self) -> Option<Vec<String>>;
+
+    /// Returns the exported [global instance](crate::Global) by name.
+    ///
+    /// # Argument
+    ///
+    /// * `name` - The name of the target exported [global instance](crate::Global).
+    ///
+    /// # Error
+    ///
+    /// If fail to find the target [global instance](crate::Global), then an error is returned.
+    fn get_global(&self, name: &str) -> WasmEdgeResult<Global>;
+
+    /// Returns the length of the exported [global instances](crate::Global) in this module instance.
+    fn global_len(&self) -> u32;
+
+    /// Returns the names of the exported [global instances](crate::Global) in this module instance.
+    fn global_names(&self) -> Option<Vec<String>>;
+
+    /// Returns the exported [table instance](crate::Table) by name.
+    ///
+    /// # Argument
+    ///
+    /// * `name` - The name of the target exported [table instance](crate::Table).
+    ///
+    /// # Error
+    ///
+    /// If fail to find the target [table instance](crate::Table), then an error is returned.
+    fn get_table(&self, name: &str) -> WasmEdgeResult<Table>;
+
+    /// Returns the length of the exported [table instances](crate::Table) in this module instance.
+    fn table_len(&self) -> u32;
+
+    /// Returns the names of the exported [table instances](crate::Table) in this module instance.
+    fn table_names(&self) -> Option<Vec<String>>;
 }
 
 #[derive(Debug)]
@@ -185,6 +221,97 @@ impl ImportModule {
             Ok(())
         }
     }
+
+    /// Adds a host-defined global with the initial value `val`.
+    ///
+    /// # Error
+    ///
+    /// If `val` does not match `val_ty`, then an error is returned.
+    pub fn add_global(
+        &mut self,
+        name: &str,
+        val_ty: ValType,
+        mutability: Mutability,
+        val: WasmVal,
+    ) -> WasmEdgeResult<()> {
+        let global_name = WasmEdgeString::new(name);
+        let global = Global::create(GlobType::create(val_ty, mutability)?, val)?;
+        unsafe {
+            ffi::WasmEdge_ModuleInstanceAddGlobal(
+                self.inner.0,
+                global_name.as_raw(),
+                global.into_raw(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a host-defined table, e.g. to let the guest register callbacks in it.
+    ///
+    /// The module instance takes ownership of `table` and frees it together with the module.
+    ///
+    /// # Error
+    ///
+    /// If `table` is [borrowed](super::instance#ownership), then an error is returned.
+    pub fn add_table(&mut self, name: &str, table: Table) -> WasmEdgeResult<()> {
+        if !table.is_owned() {
+            return Err(borrowed_error("table", name));
+        }
+        let table_name = WasmEdgeString::new(name);
+        unsafe {
+            ffi::WasmEdge_ModuleInstanceAddTable(
+                self.inner.0,
+                table_name.as_raw(),
+                table.into_raw(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a host-provided memory, which guests can import and share.
+    ///
+    /// The module instance takes ownership of `memory` and frees it together with the module.
+    ///
+    /// # Error
+    ///
+    /// If `memory` is [borrowed](super::instance#ownership), then an error is returned.
+    pub fn add_memory(&mut self, name: &str, memory: Memory) -> WasmEdgeResult<()> {
+        if !memory.is_owned() {
+            return Err(borrowed_error("memory", name));
+        }
+        let mem_name = WasmEdgeString::new(name);
+        unsafe {
+            ffi::WasmEdge_ModuleInstanceAddMemory(
+                self.inner.0,
+                mem_name.as_raw(),
+                memory.into_raw(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds a host function whose arguments are converted into `A`, see [WasmArgs](super::wasm_ptr::WasmArgs).
+    pub fn add_typed_func<T: Sized, A: WasmArgs>(
+        &mut self,
+        name: &str,
+        data: *mut T,
+        returns: Vec<ValType>,
+        real_fn: fn(Option<&mut T>, A) -> Result<Vec<WasmVal>, u32>,
+        cost: u64,
+    ) -> WasmEdgeResult<()> {
+        let params = A::val_types().unwrap_or_default();
+        let func_name = WasmEdgeString::new(name);
+        unsafe {
+            let func = Function::create_typed((params, returns), real_fn, data, cost)?;
+            ffi::WasmEdge_ModuleInstanceAddFunction(
+                self.inner.0,
+                func_name.as_raw(),
+                func.inner.0 as *mut _,
+            );
+
+            Ok(())
+        }
+    }
 }
 
 impl ImportModule {
@@ -241,9 +368,33 @@ impl<T: AsInnerInstance> AsInstance for T {
             true => Err(WasmEdgeError::Instance(InstanceError::NotFoundMem(
                 name.to_string(),
             ))),
-            false => Ok(Memory {
-                inner: InnerMemory(ctx),
-            }),
+            false => Ok(Memory::from_raw(ctx)),
+        }
+    }
+
+    fn get_global(&self, name: &str) -> WasmEdgeResult<Global> {
+        let global_name: WasmEdgeString = WasmEdgeString::new(name);
+        let ctx = unsafe {
+            ffi::WasmEdge_ModuleInstanceFindGlobal(self.get_mut_ptr(), global_name.as_raw())
+        };
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::Instance(InstanceError::NotFoundGlobal(
+                name.to_string(),
+            ))),
+            false => Ok(Global::from_raw(ctx)),
+        }
+    }
+
+    fn get_table(&self, name: &str) -> WasmEdgeResult<Table> {
+        let table_name: WasmEdgeString = WasmEdgeString::new(name);
+        let ctx = unsafe {
+            ffi::WasmEdge_ModuleInstanceFindTable(self.get_mut_ptr(), table_name.as_raw())
+        };
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::Instance(InstanceError::NotFoundTable(
+                name.to_string(),
+            ))),
+            false => Ok(Table::from_raw(ctx)),
         }
     }
 
@@ -306,4 +457,64 @@ impl<T: AsInnerInstance> AsInstance for T {
             false => None,
         }
     }
+
+    /// Returns the length of the exported [global instances](crate::Global) in this module instance.
+    fn global_len(&self) -> u32 {
+        unsafe { ffi::WasmEdge_ModuleInstanceListGlobalLength(self.get_mut_ptr()) }
+    }
+
+    /// Returns the names of the exported [global instances](crate::Global) in this module instance.
+    fn global_names(&self) -> Option<Vec<String>> {
+        let len_global_names = self.global_len();
+        match len_global_names > 0 {
+            true => {
+                let mut global_names = Vec::with_capacity(len_global_names as usize);
+                unsafe {
+                    ffi::WasmEdge_ModuleInstanceListGlobal(
+                        self.get_mut_ptr(),
+                        global_names.as_mut_ptr(),
+                        len_global_names,
+                    );
+                    global_names.set_len(len_global_names as usize);
+                }
+
+                let names = global_names
+                    .into_iter()
+                    .map(|x| x.into())
+                    .collect::<Vec<String>>();
+                Some(names)
+            }
+            false => None,
+        }
+    }
+
+    /// Returns the length of the exported [table instances](crate::Table) in this module instance.
+    fn table_len(&self) -> u32 {
+        unsafe { ffi::WasmEdge_ModuleInstanceListTableLength(self.get_mut_ptr()) }
+    }
+
+    /// Returns the names of the exported [table instances](crate::Table) in this module instance.
+    fn table_names(&self) -> Option<Vec<String>> {
+        let len_table_names = self.table_len();
+        match len_table_names > 0 {
+            true => {
+                let mut table_names = Vec::with_capacity(len_table_names as usize);
+                unsafe {
+                    ffi::WasmEdge_ModuleInstanceListTable(
+                        self.get_mut_ptr(),
+                        table_names.as_mut_ptr(),
+                        len_table_names,
+                    );
+                    table_names.set_len(len_table_names as usize);
+                }
+
+                let names = table_names
+                    .into_iter()
+                    .map(|x| x.into())
+                    .collect::<Vec<String>>();
+                Some(names)
+            }
+            false => None,
+        }
+    }
 }
@@ -0,0 +1,375 @@
+//! Defines recording and replaying the async host calls of an [AsyncLinker], so that a failure that
+//! depends on what the host returned can be reproduced.
+//!
+//! In record mode every call of an async host function is logged, once its future completes, as one
+//! JSON line: the order it was made in, the import name, the arguments, the returned values or the
+//! error, and how many polls the future took. In replay mode the calls are served from such a log in
+//! the same order, without running the host futures; each replayed future takes as many polls as the
+//! recorded one, so the guest is unwound and rewound at the same points.
+//!
+//! A replayed call whose import name or arguments differ from the log fails, as the guest has
+//! diverged from the recording. References cannot be logged.
+//!
+//! Only the values a host call returns are logged. Writes a host future makes to guest memory, e.g.
+//! filling a buffer the guest passed, are not recorded and not replayed, so a guest that reads such a
+//! buffer sees different data on replay.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+use serde::{Deserialize, Serialize};
+use wasmedge_sys::ffi;
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::async_mod::{AsyncLinker, ResultFuture};
+use super::snapshot::SnapshotValue;
+use super::types::WasmVal;
+
+/// A host call of the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    /// The order the call was made in, starting at 0.
+    pub seq: u64,
+    /// The import called, as `module.name`.
+    pub func: String,
+    pub args: Vec<SnapshotValue>,
+    /// The returned values, or the message of the returned error.
+    pub result: Result<Vec<SnapshotValue>, String>,
+    /// The number of times the future was polled, including the one it completed on.
+    pub polls: u32,
+}
+
+#[derive(Default)]
+pub(crate) enum HostCallLog {
+    #[default]
+    Off,
+    Record(Arc<Mutex<Recorder>>),
+    Replay(VecDeque<HostCall>),
+}
+
+pub(crate) struct Recorder {
+    writer: BufWriter<File>,
+    next_seq: u64,
+    /// The first error writing the log, reported by [AsyncLinker::stop_host_call_log].
+    error: Option<String>,
+}
+
+impl Recorder {
+    fn write(&mut self, call: &HostCall) {
+        if self.error.is_some() {
+            return;
+        }
+        let r = serde_json::to_string(call)
+            .map_err(|e| e.to_string())
+            .and_then(|line| writeln!(self.writer, "{}", line).map_err(|e| e.to_string()))
+            .and_then(|_| self.writer.flush().map_err(|e| e.to_string()));
+        if let Err(e) = r {
+            self.error = Some(e);
+        }
+    }
+}
+
+/// A host call being recorded, written to the log once its future completes.
+pub(crate) struct Recording {
+    recorder: Arc<Mutex<Recorder>>,
+    seq: u64,
+    func: String,
+    args: Vec<WasmVal>,
+}
+
+impl Recording {
+    fn finish(&self, polls: u32, result: &WasmEdgeResult<Vec<WasmVal>>) {
+        let mut recorder = self.recorder.lock().unwrap();
+        let call = log_values(&self.args).and_then(|args| {
+            Ok(HostCall {
+                seq: self.seq,
+                func: self.func.clone(),
+                args,
+                result: match result {
+                    Ok(v) => Ok(log_values(v)?),
+                    Err(e) => Err(e.to_string()),
+                },
+                polls,
+            })
+        });
+        match call {
+            Ok(call) => recorder.write(&call),
+            Err(e) => {
+                recorder.error.get_or_insert(e.to_string());
+            }
+        }
+    }
+}
+
+fn log_error(msg: impl std::fmt::Display) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("host call log: {}", msg))
+}
+
+fn log_values(vals: &[WasmVal]) -> WasmEdgeResult<Vec<SnapshotValue>> {
+    vals.iter().map(SnapshotValue::try_from).collect()
+}
+
+/// Reads the arguments of a host call.
+pub(crate) fn raw_args(params: *const ffi::WasmEdge_Value, param_len: u32) -> Vec<WasmVal> {
+    let raw = unsafe { std::slice::from_raw_parts(params, param_len as usize) };
+    raw.iter().map(|r| (*r).into()).collect()
+}
+
+/// Counts the polls of `fut`, and logs the call once it completes.
+pub(crate) fn record_host_call<'a>(
+    mut fut: Pin<ResultFuture<'a>>,
+    recording: Option<Recording>,
+) -> Pin<ResultFuture<'a>> {
+    let recording = match recording {
+        Some(recording) => recording,
+        None => return fut,
+    };
+    let mut polls = 0;
+    Box::pin(std::future::poll_fn(move |cx| {
+        polls += 1;
+        let r = fut.as_mut().poll(cx);
+        if let Poll::Ready(result) = &r {
+            recording.finish(polls, result);
+        }
+        r
+    }))
+}
+
+/// Returns a future that completes with `result` on its `polls`-th poll.
+fn replayed<'a>(polls: u32, result: WasmEdgeResult<Vec<WasmVal>>) -> Pin<ResultFuture<'a>> {
+    let mut remaining = polls.max(1);
+    let mut result = Some(result);
+    Box::pin(std::future::poll_fn(move |cx| {
+        remaining -= 1;
+        if remaining == 0 {
+            Poll::Ready(result.take().unwrap_or_else(|| Ok(vec![])))
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }))
+}
+
+impl AsyncLinker {
+    /// Starts logging every async host call to the file at `path`, replacing it.
+    pub fn record_host_calls<P: AsRef<Path>>(&mut self, path: P) -> WasmEdgeResult<()> {
+        let file = File::create(path).map_err(log_error)?;
+        self.host_call_log = HostCallLog::Record(Arc::new(Mutex::new(Recorder {
+            writer: BufWriter::new(file),
+            next_seq: 0,
+            error: None,
+        })));
+        Ok(())
+    }
+
+    /// Starts serving the async host calls from the log at `path` instead of running them.
+    pub fn replay_host_calls<P: AsRef<Path>>(&mut self, path: P) -> WasmEdgeResult<()> {
+        let log = std::fs::read_to_string(path).map_err(log_error)?;
+        let mut calls = log
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str::<HostCall>(line).map_err(log_error))
+            .collect::<WasmEdgeResult<Vec<_>>>()?;
+        calls.sort_by_key(|call| call.seq);
+        self.host_call_log = HostCallLog::Replay(calls.into());
+        Ok(())
+    }
+
+    /// Stops recording or replaying, and runs the host calls again.
+    ///
+    /// # Error
+    ///
+    /// If a call could not be written to the log while recording, then an error is returned.
+    pub fn stop_host_call_log(&mut self) -> WasmEdgeResult<()> {
+        match std::mem::take(&mut self.host_call_log) {
+            HostCallLog::Record(recorder) => match recorder.lock().unwrap().error.take() {
+                Some(e) => Err(log_error(e)),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the recorded future of a host call in replay mode.
+    pub(crate) fn replayed_host_call<'a>(
+        &mut self,
+        func: &str,
+        args: &[WasmVal],
+    ) -> Option<Pin<ResultFuture<'a>>> {
+        let calls = match &mut self.host_call_log {
+            HostCallLog::Replay(calls) => calls,
+            _ => return None,
+        };
+        let call = match calls.pop_front() {
+            Some(call) => call,
+            None => {
+                return Some(replayed(
+                    1,
+                    Err(log_error(format!("no recorded call left for {}", func))),
+                ))
+            }
+        };
+        let args = log_values(args);
+        if call.func != func || args.as_ref().ok() != Some(&call.args) {
+            return Some(replayed(
+                1,
+                Err(log_error(format!(
+                    "call {} diverged: recorded {}{:?}, got {}{:?}",
+                    call.seq, call.func, call.args, func, args
+                ))),
+            ));
+        }
+        let result = match call.result {
+            Ok(v) => Ok(v.into_iter().map(Into::into).collect()),
+            Err(e) => Err(WasmEdgeError::Operation(e)),
+        };
+        Some(replayed(call.polls, result))
+    }
+
+    /// Starts recording a host call in record mode.
+    pub(crate) fn host_call_recording(
+        &mut self,
+        func: &str,
+        args: &[WasmVal],
+    ) -> Option<Recording> {
+        let recorder = match &self.host_call_log {
+            HostCallLog::Record(recorder) => recorder.clone(),
+            _ => return None,
+        };
+        let seq = {
+            let mut recorder = recorder.lock().unwrap();
+            recorder.next_seq += 1;
+            recorder.next_seq - 1
+        };
+        Some(Recording {
+            recorder,
+            seq,
+            func: func.to_string(),
+            args: args.to_vec(),
+        })
+    }
+
+    /// Returns `true` if host calls are recorded or replayed.
+    pub(crate) fn logs_host_calls(&self) -> bool {
+        !matches!(self.host_call_log, HostCallLog::Off)
+    }
+
+    /// Keeps the key of the host function `real_fn`, imported as `name`, and returns it.
+    pub(crate) fn host_func_key(&mut self, real_fn: *const (), name: String) -> *const HostFunc {
+        let func = Box::new(HostFunc { real_fn, name });
+        let key = &*func as *const HostFunc;
+        self.host_funcs.push(func);
+        key
+    }
+}
+
+/// The key an async host function is bound with: the function to run and the import name its calls
+/// are logged under.
+///
+/// Each registration gets its own key, so functions registered under several names, or folded into
+/// the same code by the compiler, are still told apart.
+pub(crate) struct HostFunc {
+    pub(crate) real_fn: *const (),
+    pub(crate) name: String,
+}
+
+/// The keys of the async host functions of a linker, boxed so that they do not move while WasmEdge
+/// holds them.
+pub(crate) type HostFuncs = Vec<Box<HostFunc>>;
+
+#[cfg(test)]
+mod tests {
+    use std::task::Context;
+
+    use super::*;
+
+    fn poll_count(mut fut: Pin<ResultFuture<'_>>) -> (u32, WasmEdgeResult<Vec<WasmVal>>) {
+        let waker = waker_fn::waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let mut polls = 0;
+        loop {
+            polls += 1;
+            if let Poll::Ready(r) = fut.as_mut().poll(&mut cx) {
+                return (polls, r);
+            }
+        }
+    }
+
+    /// A future completing with `result` on its `polls`-th poll.
+    fn host_future(polls: u32, result: Vec<WasmVal>) -> Pin<ResultFuture<'static>> {
+        replayed(polls, Ok(result))
+    }
+
+    #[test]
+    fn replayed_future_takes_the_recorded_polls() {
+        let (polls, r) = poll_count(replayed(3, Ok(vec![WasmVal::I32(7)])));
+        assert_eq!(polls, 3);
+        assert!(matches!(r.unwrap()[..], [WasmVal::I32(7)]));
+
+        let (polls, r) = poll_count(replayed(0, Err(log_error("failed"))));
+        assert_eq!(polls, 1);
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn records_calls_as_json_lines() {
+        let path = std::env::temp_dir().join(format!("host_calls_{}.jsonl", std::process::id()));
+        let recorder = Arc::new(Mutex::new(Recorder {
+            writer: BufWriter::new(File::create(&path).unwrap()),
+            next_seq: 0,
+            error: None,
+        }));
+        for (seq, polls) in [(0, 2), (1, 1)] {
+            let recording = Recording {
+                recorder: recorder.clone(),
+                seq,
+                func: "host.read".to_string(),
+                args: vec![WasmVal::I32(4), WasmVal::I64(-1)],
+            };
+            let fut =
+                record_host_call(host_future(polls, vec![WasmVal::F32(0.5)]), Some(recording));
+            assert_eq!(poll_count(fut).0, polls);
+        }
+        assert!(recorder.lock().unwrap().error.is_none());
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let calls = log
+            .lines()
+            .map(|line| serde_json::from_str::<HostCall>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [0, 1].map(|seq| HostCall {
+                seq,
+                func: "host.read".to_string(),
+                args: vec![SnapshotValue::I32(4), SnapshotValue::I64(-1)],
+                result: Ok(vec![SnapshotValue::F32(0.5)]),
+                polls: 2 - seq as u32,
+            })
+        );
+    }
+
+    #[test]
+    fn logs_errors_and_rejects_other_values() {
+        let call = HostCall {
+            seq: 0,
+            func: "host.fail".to_string(),
+            args: vec![],
+            result: Err("operation failed".to_string()),
+            polls: 1,
+        };
+        let line = serde_json::to_string(&call).unwrap();
+        assert_eq!(serde_json::from_str::<HostCall>(&line).unwrap(), call);
+
+        assert!(log_values(&[WasmVal::I32(1)]).is_ok());
+        assert!(log_values(&[WasmVal::None]).is_err());
+    }
+}
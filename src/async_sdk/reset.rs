@@ -0,0 +1,125 @@
+//! Defines resetting an instance to its state right after instantiation, so that a pooled linker
+//! does not leak one request's memory, globals and tables into the next.
+//!
+//! The linker keeps the [AstModule](super::ast_module::AstModule) it instantiated and a reset
+//! instantiates it again in place of the old instance, so every memory, global and table is reset,
+//! exported or not, and the start function runs again. Handles to the memories, globals, tables and
+//! functions of the old instance must not be used after a reset.
+
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::ast_module::AstModule;
+use super::async_mod::AsyncLinker;
+use super::error::{self, Context, Operation};
+use super::Linker;
+
+impl Linker {
+    /// Replaces the active instance with a fresh instance of the same module.
+    ///
+    /// # Error
+    ///
+    /// If no module is active or a call is running, then an error is returned. If the module fails to
+    /// instantiate again, then an error is returned and no module is active anymore.
+    pub fn reset(&mut self) -> error::Result<()> {
+        let module = self.resettable_module().context(Operation::Instantiate)?;
+        // dropping the old instance unregisters it, so the fresh one can take its name
+        self.inst = None;
+        self.inst = Some(self.executor.register_active_module(&module)?);
+        self.track_memories().context(Operation::Instantiate)
+    }
+
+    fn resettable_module(&self) -> WasmEdgeResult<AstModule> {
+        if !self.call_stack.is_empty() {
+            return Err(reset_error("a call is running"));
+        }
+        match (&self.inst, &self.module) {
+            (Some(_), Some(module)) => Ok(module.clone()),
+            _ => Err(reset_error("no module is active")),
+        }
+    }
+}
+
+fn reset_error(msg: &str) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("reset: {}", msg))
+}
+
+impl AsyncLinker {
+    /// Replaces the instance with a fresh instance of the same module, see [Linker::reset].
+    ///
+    /// A suspended call, its pending host futures and its green tasks are dropped, and the asyncify
+    /// state is cleared.
+    pub fn reset(&mut self) -> error::Result<()> {
+        if self.call_depth > 0 {
+            return Err(reset_error("a call is running")).context(Operation::Instantiate);
+        }
+        self.suspended = None;
+        self.tasks.clear();
+        self.task_futures().clear();
+        self.real_linker.reset()?;
+        self.asyncify_normal()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::config::Config;
+    use super::super::types::WasmVal;
+    use super::super::AsLinker;
+    use super::*;
+
+    const COUNTER: &str = r#"(module
+        (memory (export "memory") 1)
+        (data (i32.const 0) "init")
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (global $hidden (mut i32) (i32.const 5))
+        (func (export "bump") (result i32)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (global.set $hidden (i32.add (global.get $hidden) (i32.const 1)))
+            ;; "test"
+            (i32.store (i32.const 0) (i32.const 0x74736574))
+            (global.get $hidden)))"#;
+
+    fn linker() -> Box<Linker> {
+        let wasm = wat::parse_str(COUNTER).unwrap();
+        let config = Some(Config::for_module(&wasm).unwrap());
+        let module = Loader::create(&config)
+            .unwrap()
+            .load_module_from_bytes(&wasm)
+            .unwrap();
+        let mut linker = Linker::new(&config).unwrap();
+        linker.active_module(&module).unwrap();
+        linker
+    }
+
+    fn count(linker: &Linker) -> WasmVal {
+        linker.get_global("count").unwrap().get_value()
+    }
+
+    #[test]
+    fn reset_restores_globals_and_memory() {
+        let mut linker = linker();
+        assert!(matches!(
+            linker.run("bump", &[]).unwrap()[..],
+            [WasmVal::I32(6)]
+        ));
+        assert!(matches!(count(&linker), WasmVal::I32(1)));
+        assert_eq!(linker.memory().unwrap().read_bytes(0, 4).unwrap(), b"test");
+
+        linker.reset().unwrap();
+        assert!(matches!(count(&linker), WasmVal::I32(0)));
+        assert_eq!(linker.memory().unwrap().read_bytes(0, 4).unwrap(), b"init");
+        // the unexported global starts over too
+        assert!(matches!(
+            linker.run("bump", &[]).unwrap()[..],
+            [WasmVal::I32(6)]
+        ));
+    }
+
+    #[test]
+    fn reset_requires_an_active_module() {
+        let mut linker = Linker::new(&None).unwrap();
+        assert!(linker.reset().is_err());
+    }
+}
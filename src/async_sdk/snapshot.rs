@@ -0,0 +1,576 @@
+//! Defines snapshots of a suspended [AsyncLinker] call, which can be restored into a fresh instance of
+//! the same module, e.g. after a process restart.
+//!
+//! A snapshot holds the exported call that is suspended and its arguments, the exported memories,
+//! the exported mutable globals, the asyncify stack and a fingerprint of the module. Memories and
+//! globals the module does not export cannot be read through WasmEdge, so snapshots are only taken
+//! of modules loaded with [snapshots](super::ast_module::Loader::snapshots) turned on.
+//! [load_async_module_from_bytes](super::ast_module::Loader::load_async_module_from_bytes) then
+//! exports every mutable global and memory, such as the `__stack_pointer` of LLVM-built modules and
+//! the state of the asyncify pass, under a `__wasmedge_global_<index>` or `__wasmedge_memory_<index>`
+//! name. These exports are left out of [AstModule::exports](super::ast_module::AstModule::exports)
+//! and [Linker::global_names].
+//!
+//! Tables are not saved: a call that changes a table, e.g. with `table.set` or `table.grow`, does not
+//! find the change after a restore.
+//!
+//! The host future the call is suspended in cannot be saved: once restored, the call is rewound into
+//! the same host function with the same arguments, which runs it again from the start.
+//!
+//! ```text
+//! let bytes = linker.snapshot()?.to_bytes();
+//! // ... in another process, after instantiating the same module
+//! let snapshot = Snapshot::from_bytes(&bytes)?;
+//! let result = linker.restore(&snapshot)?.await;
+//! ```
+
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use serde::{Deserialize, Serialize};
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{ExportKind, ExportSection, Module, SectionId};
+use wasmedge_types::error::{InstanceError, WasmEdgeError};
+use wasmedge_types::{Mutability, WasmEdgeResult};
+use wasmparser::{ExternalKind, Parser, Payload, TypeRef};
+
+use super::async_mod::{AsyncLinker, WasmEdgeResultFuture};
+use super::error::{self, Context, Error, Operation};
+use super::fuel::comes_after;
+use super::green_thread::ASYNCIFY_MEMORY;
+use super::instance::memory::Memory;
+use super::module::AsInstance;
+use super::types::WasmVal;
+use super::Linker;
+
+const MAGIC: &[u8; 8] = b"WESNAP\0\x02";
+
+/// The prefix of the export names given to the state a module does not export itself.
+pub(crate) const STATE_EXPORT_PREFIX: &str = "__wasmedge_";
+/// The export names given to mutable globals and memories the module does not export itself.
+const GLOBAL_EXPORT_PREFIX: &str = "__wasmedge_global_";
+const MEMORY_EXPORT_PREFIX: &str = "__wasmedge_memory_";
+const PAGE_SIZE: u32 = 65536;
+
+/// A value of a snapshot. References cannot be saved.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SnapshotValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    V128(i128),
+}
+
+impl TryFrom<&WasmVal> for SnapshotValue {
+    type Error = WasmEdgeError;
+
+    fn try_from(val: &WasmVal) -> WasmEdgeResult<Self> {
+        match *val {
+            WasmVal::I32(v) => Ok(SnapshotValue::I32(v)),
+            WasmVal::I64(v) => Ok(SnapshotValue::I64(v)),
+            WasmVal::F32(v) => Ok(SnapshotValue::F32(v)),
+            WasmVal::F64(v) => Ok(SnapshotValue::F64(v)),
+            WasmVal::V128(v) => Ok(SnapshotValue::V128(v)),
+            _ => Err(snapshot_error("references cannot be saved")),
+        }
+    }
+}
+
+impl From<SnapshotValue> for WasmVal {
+    fn from(val: SnapshotValue) -> Self {
+        match val {
+            SnapshotValue::I32(v) => WasmVal::I32(v),
+            SnapshotValue::I64(v) => WasmVal::I64(v),
+            SnapshotValue::F32(v) => WasmVal::F32(v),
+            SnapshotValue::F64(v) => WasmVal::F64(v),
+            SnapshotValue::V128(v) => WasmVal::V128(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemorySnapshot {
+    pub name: String,
+    pub pages: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlobalSnapshot {
+    pub name: String,
+    pub value: SnapshotValue,
+}
+
+/// The state of a suspended call, see the [module](self) docs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The export that was called.
+    pub call: String,
+    pub args: Vec<SnapshotValue>,
+    pub memories: Vec<MemorySnapshot>,
+    pub globals: Vec<GlobalSnapshot>,
+    pub asyncify_stack: Vec<u8>,
+    /// The fingerprint of the module the snapshot was taken from.
+    pub fingerprint: u64,
+}
+
+fn snapshot_error(msg: impl std::fmt::Display) -> WasmEdgeError {
+    WasmEdgeError::Operation(format!("snapshot: {}", msg))
+}
+
+impl Linker {
+    fn memory_instance(&self, name: &str) -> WasmEdgeResult<Memory> {
+        match &self.inst {
+            Some(inst) => inst.get_memory(name),
+            None => Err(WasmEdgeError::Instance(InstanceError::NotFoundMem(
+                name.to_string(),
+            ))),
+        }
+    }
+
+    /// Resizes the exported memory `name` to `pages`. Memories cannot shrink, so the pages past
+    /// `pages` are zeroed instead.
+    pub(crate) fn resize_memory(&mut self, name: &str, pages: u32) -> WasmEdgeResult<Memory> {
+        let mut mem = self.memory_instance(name)?;
+        let size = mem.size();
+        if size < pages {
+            mem.grow(pages - size)?;
+        } else if size > pages {
+            let tail = (size - pages) * PAGE_SIZE;
+            mem.set_data(vec![0; tail as usize], pages * PAGE_SIZE)?;
+        }
+        Ok(mem)
+    }
+
+    /// Captures the exported memories, except those named in `skip`.
+    pub(crate) fn capture_memories(&self, skip: &[&str]) -> WasmEdgeResult<Vec<MemorySnapshot>> {
+        let inst = match &self.inst {
+            Some(inst) => inst,
+            None => return Ok(vec![]),
+        };
+        let mut memories = vec![];
+        for name in inst.mem_names().unwrap_or_default() {
+            if skip.contains(&name.as_str()) {
+                continue;
+            }
+            let mem = inst.get_memory(&name)?;
+            let pages = mem.size();
+            memories.push(MemorySnapshot {
+                data: mem.get_data(0, pages * PAGE_SIZE)?,
+                name,
+                pages,
+            });
+        }
+        Ok(memories)
+    }
+
+    /// Captures the exported mutable globals.
+    pub(crate) fn capture_globals(&self) -> WasmEdgeResult<Vec<GlobalSnapshot>> {
+        let inst = match &self.inst {
+            Some(inst) => inst,
+            None => return Ok(vec![]),
+        };
+        let mut globals = vec![];
+        for name in inst.global_names().unwrap_or_default() {
+            let global = inst.get_global(&name)?;
+            if global.get_type()?.1 == Mutability::Var {
+                globals.push(GlobalSnapshot {
+                    value: SnapshotValue::try_from(&global.get_value())?,
+                    name,
+                });
+            }
+        }
+        Ok(globals)
+    }
+
+    /// Writes `memories` back, see [resize_memory](Linker::resize_memory).
+    pub(crate) fn restore_memories(&mut self, memories: &[MemorySnapshot]) -> WasmEdgeResult<()> {
+        for snapshot in memories {
+            self.resize_memory(&snapshot.name, snapshot.pages)?
+                .set_data(&snapshot.data, 0)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn restore_globals(&mut self, globals: &[GlobalSnapshot]) -> WasmEdgeResult<()> {
+        for snapshot in globals {
+            self.get_global(&snapshot.name)?
+                .set_value(snapshot.value.into())?;
+        }
+        Ok(())
+    }
+}
+
+impl AsyncLinker {
+    /// Captures the call that is suspended between two polls.
+    ///
+    /// # Error
+    ///
+    /// If the module was not loaded with [snapshots](super::ast_module::Loader::snapshots) turned
+    /// on, no call is suspended, green tasks are running, or a global or an argument holds a
+    /// reference, then an error is returned.
+    pub fn snapshot(&self) -> error::Result<Snapshot> {
+        let state_exported = self
+            .real_linker
+            .module
+            .as_ref()
+            .map_or(false, |module| module.state_exported);
+        if !state_exported {
+            return Err(Error::new(
+                Operation::Asyncify,
+                snapshot_error("the module was not loaded with snapshots turned on"),
+            ));
+        }
+        let (call, args) = self
+            .suspended
+            .as_ref()
+            .ok_or_else(|| snapshot_error("no call is suspended"))
+            .context(Operation::Asyncify)?;
+        if !self.tasks.is_empty() {
+            return Err(Error::new(
+                Operation::Asyncify,
+                snapshot_error("green tasks cannot be saved"),
+            ));
+        }
+
+        let linker = &self.real_linker;
+        Ok(Snapshot {
+            call: call.clone(),
+            args: args
+                .iter()
+                .map(SnapshotValue::try_from)
+                .collect::<WasmEdgeResult<_>>()
+                .context(Operation::Asyncify)?,
+            memories: linker
+                .capture_memories(&[ASYNCIFY_MEMORY])
+                .context(Operation::Asyncify)?,
+            globals: linker.capture_globals().context(Operation::Asyncify)?,
+            asyncify_stack: self.asyncify_stack()?,
+            fingerprint: linker.fingerprint,
+        })
+    }
+
+    /// Restores `snapshot` into the active module, which must be a fresh instance of the module the
+    /// snapshot was taken from, and returns the restored call to be awaited.
+    ///
+    /// # Error
+    ///
+    /// If a call is suspended, or the active module is not the module the snapshot was taken from,
+    /// then an error is returned.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> error::Result<WasmEdgeResultFuture> {
+        if self.suspended.is_some() {
+            return Err(Error::new(
+                Operation::Asyncify,
+                snapshot_error("a call is suspended"),
+            ));
+        }
+        if self.real_linker.fingerprint != snapshot.fingerprint {
+            return Err(Error::new(
+                Operation::Asyncify,
+                snapshot_error("the snapshot was taken from another module"),
+            ));
+        }
+
+        let linker = &mut self.real_linker;
+        linker
+            .restore_memories(&snapshot.memories)
+            .and_then(|_| linker.restore_globals(&snapshot.globals))
+            .context(Operation::Asyncify)?;
+        let stack_pages = (snapshot.asyncify_stack.len() as u32).div_ceil(PAGE_SIZE);
+        self.real_linker
+            .resize_memory(ASYNCIFY_MEMORY, stack_pages)
+            .context(Operation::Asyncify)?;
+        self.set_asyncify_stack(&snapshot.asyncify_stack)?;
+        self.task_futures().clear();
+
+        // put the call into the unwound state, as between two polls
+        self.asyncify_interrupt()?;
+        let args = snapshot.args.iter().map(|&v| v.into()).collect::<Vec<_>>();
+        self.suspended = Some((snapshot.call.clone(), args.clone()));
+        Ok(self.call(&snapshot.call, args))
+    }
+}
+
+impl Snapshot {
+    /// Encodes the snapshot in a compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        // writing into a `Vec` cannot fail, and the derived impls only use supported types
+        bincode::serialize_into(&mut bytes, self).expect("snapshots always serialize");
+        bytes
+    }
+
+    /// Decodes a snapshot encoded by [to_bytes](Snapshot::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> WasmEdgeResult<Self> {
+        match bytes.strip_prefix(MAGIC) {
+            Some(bytes) => bincode::deserialize(bytes).map_err(snapshot_error),
+            None => Err(snapshot_error("not a snapshot")),
+        }
+    }
+}
+
+/// Returns a fingerprint of the module `wasm`, the 64-bit FNV-1a hash of its bytes.
+///
+/// Unlike [DefaultHasher](std::collections::hash_map::DefaultHasher), the hash does not change
+/// between Rust releases, so snapshots can be restored by a different build.
+pub(crate) fn fingerprint(wasm: &[u8]) -> u64 {
+    wasm.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The mutable globals and the memories a module does not export, by index.
+#[derive(Debug, Default)]
+struct UnexportedState {
+    globals: Vec<u32>,
+    memories: Vec<u32>,
+}
+
+impl UnexportedState {
+    fn read(wasm: &[u8]) -> Result<Self, wasmparser::BinaryReaderError> {
+        let mut state = UnexportedState::default();
+        let mut globals = 0;
+        let mut memories = 0;
+        let mut exported_globals = HashSet::new();
+        let mut exported_memories = HashSet::new();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        match import?.ty {
+                            TypeRef::Global(ty) => {
+                                if ty.mutable {
+                                    state.globals.push(globals);
+                                }
+                                globals += 1;
+                            }
+                            TypeRef::Memory(_) => {
+                                state.memories.push(memories);
+                                memories += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    state.memories.extend(memories..memories + reader.count());
+                    memories += reader.count();
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        if global?.ty.mutable {
+                            state.globals.push(globals);
+                        }
+                        globals += 1;
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        match export.kind {
+                            ExternalKind::Global => exported_globals.insert(export.index),
+                            ExternalKind::Memory => exported_memories.insert(export.index),
+                            _ => false,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+        state.globals.retain(|idx| !exported_globals.contains(idx));
+        state
+            .memories
+            .retain(|idx| !exported_memories.contains(idx));
+        Ok(state)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.globals.is_empty() && self.memories.is_empty()
+    }
+}
+
+/// Re-encodes a module with an export for every mutable global and memory it does not export.
+struct StateExporter {
+    state: UnexportedState,
+    exports_added: bool,
+}
+
+impl StateExporter {
+    fn add_exports(&mut self, exports: &mut ExportSection) {
+        for idx in &self.state.globals {
+            exports.export(
+                &format!("{}{}", GLOBAL_EXPORT_PREFIX, idx),
+                ExportKind::Global,
+                *idx,
+            );
+        }
+        for idx in &self.state.memories {
+            exports.export(
+                &format!("{}{}", MEMORY_EXPORT_PREFIX, idx),
+                ExportKind::Memory,
+                *idx,
+            );
+        }
+        self.exports_added = true;
+    }
+}
+
+impl Reencode for StateExporter {
+    type Error = Infallible;
+
+    fn parse_export_section(
+        &mut self,
+        exports: &mut ExportSection,
+        section: wasmparser::ExportSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_export_section(self, exports, section)?;
+        self.add_exports(exports);
+        Ok(())
+    }
+
+    /// Adds the export section the module does not have.
+    fn intersperse_section_hook(
+        &mut self,
+        module: &mut Module,
+        _after: Option<SectionId>,
+        before: Option<SectionId>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        if !self.exports_added && comes_after(before, SectionId::Export) {
+            let mut exports = ExportSection::new();
+            self.add_exports(&mut exports);
+            module.section(&exports);
+        }
+        Ok(())
+    }
+}
+
+fn export_error(e: impl std::fmt::Display) -> Error {
+    Error::new(
+        Operation::Asyncify,
+        snapshot_error(format!("cannot export the module state: {}", e)),
+    )
+}
+
+/// Exports every mutable global and memory of `wasm`, including those the asyncify pass adds, so that
+/// [snapshots](AsyncLinker::snapshot) capture the whole state of the module.
+pub(crate) fn export_state(wasm: &[u8]) -> error::Result<Vec<u8>> {
+    let state = UnexportedState::read(wasm).map_err(export_error)?;
+    if state.is_empty() {
+        return Ok(wasm.to_vec());
+    }
+
+    let mut exporter = StateExporter {
+        state,
+        exports_added: false,
+    };
+    let mut module = Module::new();
+    exporter
+        .parse_core_module(&mut module, Parser::new(0), wasm)
+        .map_err(export_error)?;
+    Ok(module.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::async_mod::AsAsyncLinker;
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            call: "_start".to_string(),
+            args: vec![SnapshotValue::I32(-1), SnapshotValue::F64(0.5)],
+            memories: vec![MemorySnapshot {
+                name: "memory".to_string(),
+                pages: 1,
+                data: vec![1, 2, 3],
+            }],
+            globals: vec![GlobalSnapshot {
+                name: "__stack_pointer".to_string(),
+                value: SnapshotValue::V128(-2),
+            }],
+            asyncify_stack: vec![4, 5],
+            fingerprint: fingerprint(b"\0asm"),
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes = snapshot().to_bytes();
+        assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot());
+    }
+
+    #[test]
+    fn rejects_other_bytes() {
+        let bytes = snapshot().to_bytes();
+        assert!(Snapshot::from_bytes(b"WESNAP\0\x01").is_err());
+        assert!(Snapshot::from_bytes(&bytes[8..]).is_err());
+        assert!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        assert_eq!(fingerprint(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(fingerprint(b"\0asm\x01"), fingerprint(b"\0asm\x02"));
+    }
+
+    #[test]
+    fn exports_unexported_state() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory 1)
+                (global (mut i32) (i32.const 0))
+                (global i32 (i32.const 0))
+                (global (export "counter") (mut i32) (i32.const 0)))"#,
+        )
+        .unwrap();
+        let exported = export_state(&wasm).unwrap();
+
+        let mut exports = vec![];
+        for payload in Parser::new(0).parse_all(&exported) {
+            if let Payload::ExportSection(reader) = payload.unwrap() {
+                for export in reader {
+                    let export = export.unwrap();
+                    exports.push((export.name.to_string(), export.kind, export.index));
+                }
+            }
+        }
+        assert_eq!(
+            exports,
+            [
+                ("counter".to_string(), ExternalKind::Global, 2),
+                ("__wasmedge_global_0".to_string(), ExternalKind::Global, 0),
+                ("__wasmedge_memory_0".to_string(), ExternalKind::Memory, 0),
+            ]
+        );
+        assert!(UnexportedState::read(&exported).unwrap().is_empty());
+    }
+
+    #[test]
+    fn state_export_names_share_the_prefix() {
+        assert!(GLOBAL_EXPORT_PREFIX.starts_with(STATE_EXPORT_PREFIX));
+        assert!(MEMORY_EXPORT_PREFIX.starts_with(STATE_EXPORT_PREFIX));
+    }
+
+    #[test]
+    fn refuses_modules_without_snapshots() {
+        let module = Loader::create(&None)
+            .unwrap()
+            .load_from_wat(r#"(module (func (export "run")))"#)
+            .unwrap();
+        let mut linker = AsyncLinker::new(&None).unwrap();
+        linker.active_module(&module).unwrap();
+        let err = linker.snapshot().unwrap_err();
+        assert!(err.to_string().contains("snapshots turned on"), "{}", err);
+    }
+
+    #[test]
+    fn adds_an_export_section() {
+        let wasm = wat::parse_str("(module (memory 1) (func))").unwrap();
+        let exported = export_state(&wasm).unwrap();
+        assert_eq!(UnexportedState::read(&wasm).unwrap().memories, [0]);
+        assert!(UnexportedState::read(&exported).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+//! Defines the runtime statistics of a linker: instruction count, cost and execution time.
+//!
+//! The instruction count and the cost are only collected when the
+//! [Config](super::config::Config) turns on `count_instructions` and `measure_cost`; otherwise they
+//! stay zero.
+//!
+//! WasmEdge only keeps cumulative counters, so the counters are read when a top-level call begins
+//! and ends, and the statistics are the difference.
+
+use std::time::{Duration, Instant};
+
+use wasmedge_sys::ffi;
+
+use super::async_mod::AsyncLinker;
+use super::executor::Executor;
+use super::Linker;
+
+/// The statistics of the running or the last top-level call.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Statistics {
+    /// The number of executed instructions.
+    pub instr_count: u64,
+    /// `instr_count` divided by `exec_time`.
+    pub instr_per_second: f64,
+    /// The total cost of the executed instructions and host functions.
+    pub total_cost: u64,
+    /// The wall time spent running guest code. Time an async call spends suspended is not counted.
+    pub exec_time: Duration,
+}
+
+/// A reading of the cumulative counters of the WasmEdge statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Counters {
+    pub(crate) instr_count: u64,
+    pub(crate) total_cost: u64,
+}
+
+impl Counters {
+    /// Returns the counts from `start` to `self`.
+    pub(crate) fn since(self, start: Counters) -> Counters {
+        Counters {
+            instr_count: self.instr_count.saturating_sub(start.instr_count),
+            total_cost: self.total_cost.saturating_sub(start.total_cost),
+        }
+    }
+
+    fn add(&mut self, other: Counters) {
+        self.instr_count = self.instr_count.saturating_add(other.instr_count);
+        self.total_cost = self.total_cost.saturating_add(other.total_cost);
+    }
+}
+
+/// The counters of the running or the last top-level call.
+#[derive(Debug, Default)]
+pub(crate) struct StatState {
+    /// The counters when the call started.
+    pub(crate) call_start: Counters,
+    /// The counts refunded to the call, see [refund_since](Executor::refund_since).
+    pub(crate) refunded: Counters,
+    /// The counters when the call ended, or `None` while it runs.
+    call_end: Option<Counters>,
+    exec_time: Duration,
+    /// Whether a call is being timed, so that re-entrant calls are not counted twice.
+    timing: bool,
+}
+
+impl Executor {
+    pub(crate) fn counters(&self) -> Counters {
+        unsafe {
+            Counters {
+                instr_count: ffi::WasmEdge_StatisticsGetInstrCount(self.inner_stat.0),
+                total_cost: ffi::WasmEdge_StatisticsGetTotalCost(self.inner_stat.0),
+            }
+        }
+    }
+
+    /// Runs `f`, counting its wall time as execution time unless an outer call already does.
+    pub(crate) fn timed<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        if self.stat_state.timing {
+            return f(self);
+        }
+        self.stat_state.timing = true;
+        let start = Instant::now();
+        let r = f(self);
+        self.stat_state.exec_time += start.elapsed();
+        self.stat_state.timing = false;
+        r
+    }
+
+    /// Starts counting a top-level call.
+    pub(crate) fn begin_call_stats(&mut self) {
+        self.reset_statistics();
+        self.stat_state.call_end = None;
+    }
+
+    /// Stops counting the top-level call, so its statistics stay readable until the next one.
+    pub(crate) fn end_call_stats(&mut self) {
+        self.stat_state.call_end = Some(self.counters());
+    }
+
+    /// Takes the counts since `start` out of the running call, as they are not part of the guest's
+    /// own work.
+    pub(crate) fn refund_since(&mut self, start: Counters) {
+        let refund = self.counters().since(start);
+        self.stat_state.refunded.add(refund);
+    }
+
+    /// Returns the counts of the running or the last call, without the refunded counts.
+    pub(crate) fn call_counters(&self) -> Counters {
+        let end = self.stat_state.call_end.unwrap_or_else(|| self.counters());
+        end.since(self.stat_state.call_start)
+            .since(self.stat_state.refunded)
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        let counters = self.call_counters();
+        let exec_time = self.stat_state.exec_time;
+        Statistics {
+            instr_count: counters.instr_count,
+            instr_per_second: match exec_time.is_zero() {
+                true => 0.0,
+                false => counters.instr_count as f64 / exec_time.as_secs_f64(),
+            },
+            total_cost: counters.total_cost,
+            exec_time,
+        }
+    }
+
+    /// Restarts the statistics of the running call from zero.
+    pub fn reset_statistics(&mut self) {
+        self.stat_state = StatState {
+            call_start: self.counters(),
+            refunded: Counters::default(),
+            call_end: self.stat_state.call_end.map(|_| self.counters()),
+            exec_time: Duration::ZERO,
+            timing: self.stat_state.timing,
+        };
+    }
+}
+
+impl Linker {
+    /// Returns the statistics of the running or the last top-level call.
+    ///
+    /// Every call made from the host starts from zero; re-entrant calls made from host functions
+    /// count towards the call they are part of. The asyncify instrumentation replayed to resume a
+    /// suspended call and the asyncify control exports are not counted, see
+    /// [metering](super::metering).
+    pub fn statistics(&self) -> Statistics {
+        self.executor.statistics()
+    }
+
+    /// Restarts the statistics of the running call from zero, e.g. from a host function to measure
+    /// the rest of the call.
+    pub fn reset_statistics(&mut self) {
+        self.executor.reset_statistics()
+    }
+}
+
+impl AsyncLinker {
+    /// Returns the statistics of the running or the last top-level call, see [Linker::statistics].
+    ///
+    /// A call is counted as a whole, across all the polls it takes.
+    pub fn statistics(&self) -> Statistics {
+        self.real_linker.statistics()
+    }
+
+    pub fn reset_statistics(&mut self) {
+        self.real_linker.reset_statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::config::Config;
+    use super::super::types::WasmVal;
+    use super::super::AsLinker;
+    use super::*;
+
+    fn linker() -> Box<Linker> {
+        let mut config = Config::create().unwrap();
+        config.count_instructions(true);
+        config.measure_cost(true);
+        let config = Some(config);
+        let module = Loader::create(&config)
+            .unwrap()
+            .load_from_wat(
+                r#"(module
+                    (func (export "add") (param i32 i32) (result i32)
+                        local.get 0
+                        local.get 1
+                        i32.add))"#,
+            )
+            .unwrap();
+        let mut linker = Linker::new(&config).unwrap();
+        linker.active_module(&module).unwrap();
+        linker
+    }
+
+    #[test]
+    fn statistics_cover_the_last_call() {
+        let mut linker = linker();
+        assert_eq!(linker.statistics(), Statistics::default());
+
+        let args = [WasmVal::I32(1), WasmVal::I32(2)];
+        linker.run("add", &args).unwrap();
+        let first = linker.statistics();
+        assert!(first.instr_count > 0);
+        assert!(first.total_cost > 0);
+
+        // a second call starts from zero instead of adding up
+        linker.run("add", &args).unwrap();
+        let second = linker.statistics();
+        assert_eq!(second.instr_count, first.instr_count);
+        assert_eq!(second.total_cost, first.total_cost);
+
+        linker.reset_statistics();
+        assert_eq!(linker.statistics().instr_count, 0);
+    }
+
+    #[test]
+    fn counters_subtract_without_underflow() {
+        let a = Counters {
+            instr_count: 10,
+            total_cost: 3,
+        };
+        let b = Counters {
+            instr_count: 4,
+            total_cost: 5,
+        };
+        assert_eq!(
+            a.since(b),
+            Counters {
+                instr_count: 6,
+                total_cost: 0,
+            }
+        );
+    }
+}
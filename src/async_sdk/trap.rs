@@ -0,0 +1,644 @@
+//! Defines the trap reports attached to trap errors: the guest call stack when the trap happened,
+//! with function indices and the names from the module's `name` section.
+//!
+//! The WasmEdge C API does not expose the interpreter's frames, so modules loaded with
+//! [Loader::trace_calls](super::ast_module::Loader::trace_calls) keep a shadow stack instead: every
+//! function of the module calls the `wasmedge_trace.enter` import with its index when it starts and
+//! `wasmedge_trace.exit` when it returns, and [Linker] keeps the functions that have not returned
+//! yet. A trap skips the `exit` calls, so the stack still holds the frames of the trap.
+//! Function indices are those of the loaded module, which has the two imports appended after its own.
+//!
+//! A module loaded without call tracing only reports the exports entered from the host: the export
+//! passed to [Linker::run] and every export re-entered from a host function while it was running.
+//!
+//! The green tasks of an [AsyncLinker](super::async_mod::AsyncLinker) share the shadow stack of the
+//! call that spawned them, so the report of a trap in a task may list frames of other tasks.
+//!
+//! Modules loaded through [load_async_module_from_bytes](super::ast_module::Loader::load_async_module_from_bytes)
+//! only keep their `name` section if [Loader::keep_names](super::ast_module::Loader::keep_names) is on.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+
+use wasm_encoder::reencode::{self, Reencode};
+use wasm_encoder::{
+    BlockType, CodeSection, EntityType, ImportSection, Instruction, Module, SectionId, TypeSection,
+};
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+use wasmparser::{CompositeInnerType, ExternalKind, KnownCustom, Name, Parser, Payload, TypeRef};
+
+use super::ast_module::AstModule;
+use super::error::{self, Error, Operation};
+use super::fuel::{comes_after, parse_custom_section};
+use super::module::ImportModule;
+use super::types::WasmVal;
+use super::Linker;
+
+/// The module of the imports traced functions call.
+pub const TRACE_MODULE: &str = "wasmedge_trace";
+
+/// The import traced functions call with their index when they start.
+pub const TRACE_ENTER: &str = "enter";
+
+/// The import traced functions call when they return.
+pub const TRACE_EXIT: &str = "exit";
+
+/// The largest number of frames a [TrapReport] holds; the outermost ones are left out.
+pub const MAX_FRAMES: usize = 128;
+
+/// The function names of a module, read from its `name` section and its exports.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FuncNames {
+    names: HashMap<u32, String>,
+    exports: HashMap<String, u32>,
+}
+
+impl FuncNames {
+    /// Reads the names of `wasm`, stopping at the first malformed section; a module without a `name`
+    /// section only gets its export names.
+    pub(crate) fn parse(wasm: &[u8]) -> Self {
+        let mut names = FuncNames::default();
+        let _ = names.read(wasm);
+        names
+    }
+
+    fn read(&mut self, wasm: &[u8]) -> Result<(), wasmparser::BinaryReaderError> {
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::ExportSection(section) => {
+                    for export in section {
+                        let export = export?;
+                        if export.kind == ExternalKind::Func {
+                            self.exports.insert(export.name.to_string(), export.index);
+                        }
+                    }
+                }
+                Payload::CustomSection(section) => {
+                    if let KnownCustom::Name(reader) = section.as_known() {
+                        for name in reader {
+                            if let Name::Function(map) = name? {
+                                for naming in map {
+                                    let naming = naming?;
+                                    self.names.insert(naming.index, naming.name.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the frame of the function `func_index`, named from the `name` section, or from an
+    /// export of it.
+    pub(crate) fn frame(&self, func_index: u32) -> Frame {
+        let name = self.names.get(&func_index).cloned().or_else(|| {
+            self.exports
+                .iter()
+                .find(|(_, idx)| **idx == func_index)
+                .map(|(export, _)| export.clone())
+        });
+        Frame {
+            func_index: Some(func_index),
+            name: name.unwrap_or_else(|| format!("func[{}]", func_index)),
+        }
+    }
+
+    /// Returns the frame of a call of the export `export`.
+    pub(crate) fn entry(&self, export: &str) -> Frame {
+        match self.exports.get(export) {
+            Some(idx) => Frame {
+                name: self
+                    .names
+                    .get(idx)
+                    .cloned()
+                    .unwrap_or_else(|| export.to_string()),
+                func_index: Some(*idx),
+            },
+            None => Frame {
+                func_index: None,
+                name: export.to_string(),
+            },
+        }
+    }
+}
+
+/// A guest function on the call stack of a trap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The index of the function in the module's function index space, if it is known.
+    pub func_index: Option<u32>,
+    /// The name of the function from the `name` section, or its export name.
+    pub name: String,
+}
+
+/// The guest call stack when a trap happened, innermost first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrapReport {
+    pub frames: Vec<Frame>,
+    /// Whether the module was loaded with [call tracing](super::ast_module::Loader::trace_calls).
+    /// Otherwise, `frames` only holds the exports entered from the host.
+    pub traced: bool,
+}
+
+impl fmt::Display for TrapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.traced {
+            true => write!(f, "guest call stack:")?,
+            false => write!(f, "entered exports:")?,
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            match frame.func_index {
+                Some(idx) => write!(f, "\n  #{} {} (func {})", i, frame.name, idx)?,
+                None => write!(f, "\n  #{} {}", i, frame.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if `code` is a WasmEdge execution-phase error, i.e. a trap.
+pub(crate) fn is_trap(code: u32) -> bool {
+    (0x80..0xA0).contains(&code)
+}
+
+/// The function types of a module, which the injected code needs.
+#[derive(Debug, Default)]
+struct FuncTypes {
+    /// The results of each type, or `None` if it is not a function type.
+    results: Vec<Option<Vec<wasmparser::ValType>>>,
+    imported_funcs: u32,
+    /// The type of each function the module defines.
+    defined: Vec<u32>,
+}
+
+impl FuncTypes {
+    fn read(wasm: &[u8]) -> Result<Self, wasmparser::BinaryReaderError> {
+        let mut types = FuncTypes::default();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::TypeSection(reader) => {
+                    for rec_group in reader {
+                        for ty in rec_group?.types() {
+                            types.results.push(match &ty.composite_type.inner {
+                                CompositeInnerType::Func(ty) => Some(ty.results().to_vec()),
+                                _ => None,
+                            });
+                        }
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if let TypeRef::Func(_) = import?.ty {
+                            types.imported_funcs += 1;
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for ty in reader {
+                        types.defined.push(ty?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(types)
+    }
+
+    /// Returns the results of the function types with more than one result, once each.
+    fn multi_results(&self) -> Vec<Vec<wasmparser::ValType>> {
+        let mut multi: Vec<Vec<wasmparser::ValType>> = vec![];
+        for ty in &self.defined {
+            if let Some(Some(results)) = self.results.get(*ty as usize) {
+                if results.len() > 1 && !multi.contains(results) {
+                    multi.push(results.clone());
+                }
+            }
+        }
+        multi
+    }
+}
+
+/// Re-encodes a module so that every function it defines calls `wasmedge_trace.enter` when it starts
+/// and `wasmedge_trace.exit` when it returns.
+///
+/// The function body is wrapped in a block, so that branches to the function's own label go through
+/// the `exit` call at the end; `return` and tail calls make it first. The imports are appended after
+/// the existing imports, so the functions defined by the module move up by two indices, in the code
+/// and in the `name` section. The types of the imports and of the blocks of multi-value functions
+/// are appended at the end of the type section.
+struct CallTracer {
+    types: FuncTypes,
+    /// The results of the appended block types, in order.
+    multi_results: Vec<Vec<wasmparser::ValType>>,
+    /// The number of type entries before the appended ones.
+    type_count: u32,
+    /// The number of function bodies encoded so far.
+    bodies: u32,
+    import_added: bool,
+}
+
+impl CallTracer {
+    fn enter_func(&self) -> u32 {
+        self.types.imported_funcs
+    }
+
+    fn exit_func(&self) -> u32 {
+        self.types.imported_funcs + 1
+    }
+
+    fn add_imports(&mut self, imports: &mut ImportSection) {
+        imports.import(
+            TRACE_MODULE,
+            TRACE_ENTER,
+            EntityType::Function(self.type_count),
+        );
+        imports.import(
+            TRACE_MODULE,
+            TRACE_EXIT,
+            EntityType::Function(self.type_count + 1),
+        );
+        self.import_added = true;
+    }
+
+    /// Returns the type of the block wrapping the body of the function of type `ty`.
+    fn block_type(&mut self, ty: u32) -> Result<BlockType, reencode::Error<Infallible>> {
+        let results = match self.types.results.get(ty as usize) {
+            Some(Some(results)) => results.clone(),
+            _ => return Ok(BlockType::Empty),
+        };
+        Ok(match results.len() {
+            0 => BlockType::Empty,
+            1 => BlockType::Result(self.val_type(results[0])?),
+            _ => {
+                let idx = self.multi_results.iter().position(|r| *r == results);
+                // every multi-value type of a defined function got a block type
+                BlockType::FunctionType(self.type_count + 2 + idx.unwrap_or_default() as u32)
+            }
+        })
+    }
+}
+
+impl Reencode for CallTracer {
+    type Error = Infallible;
+
+    fn function_index(&mut self, func: u32) -> u32 {
+        match func < self.types.imported_funcs {
+            true => func,
+            false => func + 2,
+        }
+    }
+
+    fn parse_type_section(
+        &mut self,
+        types: &mut TypeSection,
+        section: wasmparser::TypeSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_type_section(self, types, section)?;
+        types
+            .ty()
+            .function(vec![wasm_encoder::ValType::I32], vec![]);
+        types.ty().function(vec![], vec![]);
+        for results in self.multi_results.clone() {
+            let results = results
+                .into_iter()
+                .map(|ty| self.val_type(ty))
+                .collect::<Result<Vec<_>, _>>()?;
+            types.ty().function(vec![], results);
+        }
+        Ok(())
+    }
+
+    fn parse_import_section(
+        &mut self,
+        imports: &mut ImportSection,
+        section: wasmparser::ImportSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_import_section(self, imports, section)?;
+        self.add_imports(imports);
+        Ok(())
+    }
+
+    fn parse_function_body(
+        &mut self,
+        code: &mut CodeSection,
+        func: wasmparser::FunctionBody<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        let defined = self.bodies;
+        self.bodies += 1;
+        let ty = self.types.defined.get(defined as usize).copied();
+        let block_type = match ty {
+            Some(ty) => self.block_type(ty)?,
+            None => BlockType::Empty,
+        };
+        let index = self.types.imported_funcs + 2 + defined;
+
+        let mut f = self.new_function_with_parsed_locals(&func)?;
+        f.instruction(&Instruction::I32Const(index as i32));
+        f.instruction(&Instruction::Call(self.enter_func()));
+        f.instruction(&Instruction::Block(block_type));
+        let mut reader = func.get_operators_reader()?;
+        while !reader.eof() {
+            let op = reader.read()?;
+            if matches!(
+                op,
+                wasmparser::Operator::Return
+                    | wasmparser::Operator::ReturnCall { .. }
+                    | wasmparser::Operator::ReturnCallIndirect { .. }
+                    | wasmparser::Operator::ReturnCallRef { .. }
+            ) {
+                f.instruction(&Instruction::Call(self.exit_func()));
+            }
+            // the `end` of the body closes the block
+            f.instruction(&self.instruction(op)?);
+        }
+        f.instruction(&Instruction::Call(self.exit_func()));
+        f.instruction(&Instruction::End);
+        code.function(&f);
+        Ok(())
+    }
+
+    /// Moves the function indices of the `name` section along with the functions.
+    fn parse_custom_section(
+        &mut self,
+        module: &mut Module,
+        section: wasmparser::CustomSectionReader<'_>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        parse_custom_section(self, module, section)
+    }
+
+    /// Adds the import section the module does not have. A module that defines functions has a type
+    /// section, which is extended in place.
+    fn intersperse_section_hook(
+        &mut self,
+        module: &mut Module,
+        _after: Option<SectionId>,
+        before: Option<SectionId>,
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        if !self.import_added && comes_after(before, SectionId::Import) {
+            let mut imports = ImportSection::new();
+            self.add_imports(&mut imports);
+            module.section(&imports);
+        }
+        Ok(())
+    }
+}
+
+fn trace_error(e: impl fmt::Display) -> Error {
+    Error::new(
+        Operation::Load,
+        WasmEdgeError::Operation(format!("cannot inject call tracing: {}", e)),
+    )
+}
+
+/// Makes every function `wasm` defines report its calls, see the [module](self) docs.
+pub(crate) fn inject_call_tracing(wasm: &[u8]) -> error::Result<Vec<u8>> {
+    let types = FuncTypes::read(wasm).map_err(trace_error)?;
+    if types.defined.is_empty() {
+        return Ok(wasm.to_vec());
+    }
+
+    let mut tracer = CallTracer {
+        multi_results: types.multi_results(),
+        type_count: types.results.len() as u32,
+        types,
+        bodies: 0,
+        import_added: false,
+    };
+    let mut module = Module::new();
+    tracer
+        .parse_core_module(&mut module, Parser::new(0), wasm)
+        .map_err(trace_error)?;
+    Ok(module.finish())
+}
+
+/// The `wasmedge_trace.enter` import.
+fn trace_enter(linker: Option<&mut Linker>, args: &[WasmVal]) -> Result<Vec<WasmVal>, u32> {
+    if let (Some(linker), [WasmVal::I32(idx)]) = (linker, args) {
+        linker.guest_stack.push(*idx as u32);
+    }
+    Ok(vec![])
+}
+
+/// The `wasmedge_trace.exit` import.
+fn trace_exit(linker: Option<&mut Linker>, _args: &[WasmVal]) -> Result<Vec<WasmVal>, u32> {
+    if let Some(linker) = linker {
+        linker.guest_stack.pop();
+    }
+    Ok(vec![])
+}
+
+impl Linker {
+    /// Registers the imports traced functions call, if `module` imports them and they are not
+    /// registered yet.
+    pub(crate) fn register_call_trace(&mut self, module: &AstModule) -> WasmEdgeResult<()> {
+        let imported = module
+            .imports()
+            .iter()
+            .any(|import| import.module == TRACE_MODULE);
+        if !imported || self.executor.import_module(TRACE_MODULE).is_some() {
+            return Ok(());
+        }
+
+        let mut import_obj = ImportModule::create(TRACE_MODULE)?;
+        let linker: *mut Linker = self;
+        import_obj.add_func(
+            TRACE_ENTER,
+            linker,
+            (vec![wasmedge_types::ValType::I32], vec![]),
+            trace_enter,
+            0,
+        )?;
+        import_obj.add_func(TRACE_EXIT, linker, (vec![], vec![]), trace_exit, 0)?;
+        self.register_import(import_obj)
+    }
+
+    /// Returns the report of a trap in the running call.
+    pub(crate) fn trap_report(&self) -> TrapReport {
+        let traced = self
+            .module
+            .as_ref()
+            .map_or(false, |module| module.call_traced);
+        let frames = match traced {
+            true => self
+                .guest_stack
+                .iter()
+                .rev()
+                .map(|idx| self.func_names.frame(*idx))
+                .collect::<Vec<_>>(),
+            false => self
+                .call_stack
+                .iter()
+                .rev()
+                .map(|name| self.func_names.entry(name))
+                .collect(),
+        };
+        TrapReport {
+            frames: frames.into_iter().take(MAX_FRAMES).collect(),
+            traced,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ast_module::Loader;
+    use super::super::AsLinker;
+    use super::*;
+    use wasmparser::{Operator, Validator, WasmFeatures};
+
+    const NESTED: &str = r#"(module
+        (import "env" "log" (func $log (param i32)))
+        (func $outer (export "run")
+            (call $middle))
+        (func $middle
+            (call $log (i32.const 1))
+            (call $inner))
+        (func $inner
+            unreachable))"#;
+
+    fn inject(wat: &str) -> Vec<u8> {
+        let wasm = inject_call_tracing(&wat::parse_str(wat).unwrap()).unwrap();
+        Validator::new_with_features(WasmFeatures::default())
+            .validate_all(&wasm)
+            .unwrap();
+        wasm
+    }
+
+    /// Returns the operators of the body of every defined function.
+    fn bodies(wasm: &[u8]) -> Vec<Vec<String>> {
+        let mut bodies = vec![];
+        for payload in Parser::new(0).parse_all(wasm) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                let ops = body
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter()
+                    .map(|op| format!("{:?}", op.unwrap()))
+                    .collect();
+                bodies.push(ops);
+            }
+        }
+        bodies
+    }
+
+    fn call(function_index: u32) -> String {
+        format!("{:?}", Operator::Call { function_index })
+    }
+
+    fn run(loader: &Loader, wat: &str) -> Error {
+        let module = loader.load_from_wat(wat).unwrap();
+        let mut linker = Linker::new(&None).unwrap();
+        linker.active_module(&module).unwrap();
+        linker.run("run", &[]).unwrap_err()
+    }
+
+    #[test]
+    fn trace_imports_are_appended() {
+        let wasm = inject(NESTED);
+        let mut imports = vec![];
+        for payload in Parser::new(0).parse_all(&wasm) {
+            if let Payload::ImportSection(reader) = payload.unwrap() {
+                for import in reader {
+                    let import = import.unwrap();
+                    imports.push((import.module.to_string(), import.name.to_string()));
+                }
+            }
+        }
+        assert_eq!(
+            imports,
+            [
+                ("env".to_string(), "log".to_string()),
+                (TRACE_MODULE.to_string(), TRACE_ENTER.to_string()),
+                (TRACE_MODULE.to_string(), TRACE_EXIT.to_string()),
+            ]
+        );
+        // the defined functions move up along with their names
+        let names = FuncNames::parse(&wasm);
+        assert_eq!(names.frame(3).name, "outer");
+        assert_eq!(names.entry("run").func_index, Some(3));
+        assert_eq!(bodies(&wasm)[0][3], call(4));
+    }
+
+    #[test]
+    fn every_function_enters_and_exits() {
+        let wasm = inject(NESTED);
+        for (i, body) in bodies(&wasm).iter().enumerate() {
+            assert_eq!(
+                body[0],
+                format!(
+                    "{:?}",
+                    Operator::I32Const {
+                        value: 3 + i as i32
+                    }
+                )
+            );
+            assert_eq!(body[1], call(1));
+            assert!(body[2].starts_with("Block"));
+            assert_eq!(body[body.len() - 2], call(2));
+        }
+    }
+
+    #[test]
+    fn returns_exit_first() {
+        let wasm = inject(
+            r#"(module
+                (func (param i32) (result i32 i64)
+                    (if (local.get 0) (then (return (i32.const 1) (i64.const 2))))
+                    (i32.const 3)
+                    (i64.const 4)))"#,
+        );
+        let body = &bodies(&wasm)[0];
+        let at = body.iter().position(|op| op == "Return").unwrap();
+        assert_eq!(body[at - 1], call(1));
+    }
+
+    #[test]
+    fn trap_lists_the_guest_call_stack() {
+        let mut loader = Loader::create(&None).unwrap();
+        loader.trace_calls(true);
+        let wat = NESTED.replace(
+            r#"(import "env" "log" (func $log (param i32)))"#,
+            r#"(func $log (param i32))"#,
+        );
+        let module = loader.load_from_wat(&wat).unwrap();
+        let mut linker = Linker::new(&None).unwrap();
+        linker.active_module(&module).unwrap();
+
+        for _ in 0..2 {
+            let err = linker.run("run", &[]).unwrap_err();
+            let report = err.trap().unwrap();
+            assert!(report.traced);
+            let frames = report
+                .frames
+                .iter()
+                .map(|frame| (frame.func_index, frame.name.as_str()))
+                .collect::<Vec<_>>();
+            // the stack of the first trap is gone by the second one
+            assert_eq!(
+                frames,
+                [(Some(5), "inner"), (Some(4), "middle"), (Some(3), "outer")]
+            );
+        }
+        assert!(linker.guest_stack.is_empty());
+    }
+
+    #[test]
+    fn untraced_trap_lists_the_entered_exports() {
+        let loader = Loader::create(&None).unwrap();
+        let err = run(&loader, "(module (func $f (export \"run\") unreachable))");
+        let report = err.trap().unwrap();
+        assert!(!report.traced);
+        assert_eq!(
+            report.frames,
+            [Frame {
+                func_index: Some(0),
+                name: "f".to_string(),
+            }]
+        );
+    }
+}
@@ -87,6 +87,17 @@ pub enum WasmVal {
     None,
 }
 
+macro_rules! impl_from_for_wasm_val {
+    ($($t:ty => $val:ident),*) => {
+        $(impl From<$t> for WasmVal {
+            fn from(n: $t) -> Self {
+                WasmVal::$val(n)
+            }
+        })*
+    };
+}
+impl_from_for_wasm_val!(i32 => I32, i64 => I64, f32 => F32, f64 => F64, i128 => V128);
+
 impl From<ffi::WasmEdge_Value> for WasmVal {
     fn from(raw_val: ffi::WasmEdge_Value) -> Self {
         unsafe {
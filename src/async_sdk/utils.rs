@@ -7,14 +7,18 @@ use wasmedge_types::{
     WasmEdgeResult,
 };
 
-pub(crate) fn check(result: WasmEdge_Result) -> WasmEdgeResult<()> {
-    let code = unsafe {
+pub(crate) fn result_code(result: WasmEdge_Result) -> u32 {
+    unsafe {
         if !WasmEdge_ResultOK(result) {
             WasmEdge_ResultGetCode(result)
         } else {
             0u32
         }
-    };
+    }
+}
+
+pub(crate) fn check(result: WasmEdge_Result) -> WasmEdgeResult<()> {
+    let code = result_code(result);
     match code {
         // Success or terminated (exit and return success)
         0x00 | 0x01 => Ok(()),
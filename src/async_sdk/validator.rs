@@ -0,0 +1,82 @@
+//! Defines WasmEdge Validator and the validation report of a module that fails to load.
+
+use std::fmt;
+
+use wasmedge_sys::ffi;
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::WasmEdgeResult;
+
+use super::ast_module::AstModule;
+use super::config::{Config, Proposal};
+use super::error::{self, check_op, Error, Operation};
+
+/// Defines a WebAssembly validator, which checks a parsed [AstModule] against the proposals turned on
+/// in its [Config](crate::Config).
+#[derive(Debug)]
+pub struct Validator {
+    pub(crate) inner: InnerValidator,
+}
+
+impl Validator {
+    pub fn create(config: &Option<Config>) -> WasmEdgeResult<Self> {
+        let config_ctx = match config {
+            Some(config) => config.inner.0,
+            None => std::ptr::null_mut(),
+        };
+        let ctx = unsafe { ffi::WasmEdge_ValidatorCreate(config_ctx) };
+        match ctx.is_null() {
+            true => Err(WasmEdgeError::ValidatorCreate),
+            false => Ok(Validator {
+                inner: InnerValidator(ctx),
+            }),
+        }
+    }
+
+    pub fn validate(&self, module: &AstModule) -> error::Result<()> {
+        unsafe {
+            check_op(
+                ffi::WasmEdge_ValidatorValidate(self.inner.0, module.inner.0),
+                Operation::Validate,
+            )
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct InnerValidator(pub(crate) *mut ffi::WasmEdge_ValidatorContext);
+impl Drop for InnerValidator {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { ffi::WasmEdge_ValidatorDelete(self.0) }
+        }
+    }
+}
+unsafe impl Send for InnerValidator {}
+unsafe impl Sync for InnerValidator {}
+
+/// Describes why a module failed to load: the error, the proposals the module needs that the
+/// [Config](crate::Config) does not turn on, and the proposals it does turn on.
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub error: Error,
+    /// Empty if the failure is not caused by a missing proposal, or if it could not be narrowed down.
+    pub required: Vec<Proposal>,
+    pub enabled: Vec<Proposal>,
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if !self.required.is_empty() {
+            write!(f, "\nthe module requires {:?}", self.required)?;
+            write!(f, "\nthe config enables {:?}", self.enabled)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationReport {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,335 @@
+//! Defines guest pointer types usable as typed host function arguments.
+//!
+//! A typed host function takes its arguments as a tuple of [WasmArg]s, e.g.
+//! `fn(Option<&mut Linker>, (WasmStr, WasmPtr<u32>)) -> Result<Vec<WasmVal>, u32>`. Pointer arguments are
+//! checked against the calling instance's default memory before the host function runs; an argument
+//! that points outside of it traps with `MemoryOutOfBounds`.
+
+use std::marker::PhantomData;
+
+use wasmedge_types::error::WasmEdgeError;
+use wasmedge_types::{ValType, WasmEdgeResult};
+
+use super::guest_memory::{GuestMemory, Pod};
+use super::types::WasmVal;
+
+/// A pointer to a `T` in guest memory, passed as one `i32`.
+#[derive(Debug)]
+pub struct WasmPtr<T> {
+    offset: u32,
+    _ty: PhantomData<T>,
+}
+
+impl<T> Clone for WasmPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for WasmPtr<T> {}
+
+impl<T: Pod> WasmPtr<T> {
+    pub fn new(offset: u32) -> Self {
+        WasmPtr {
+            offset,
+            _ty: PhantomData,
+        }
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn read(&self, mem: &GuestMemory) -> WasmEdgeResult<T> {
+        mem.read(self.offset)
+    }
+
+    pub fn write(&self, mem: &mut GuestMemory, val: T) -> WasmEdgeResult<()> {
+        mem.write(self.offset, val)
+    }
+}
+
+/// A slice of `len` `T`s in guest memory, passed as an `i32` pointer followed by an `i32` length.
+#[derive(Debug)]
+pub struct WasmSlice<T> {
+    offset: u32,
+    len: u32,
+    _ty: PhantomData<T>,
+}
+
+impl<T> Clone for WasmSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for WasmSlice<T> {}
+
+impl<T: Pod> WasmSlice<T> {
+    pub fn new(offset: u32, len: u32) -> Self {
+        WasmSlice {
+            offset,
+            len,
+            _ty: PhantomData,
+        }
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the length in bytes.
+    ///
+    /// # Error
+    ///
+    /// If the length does not fit in the 32-bit address space, then an error is returned.
+    pub fn byte_len(&self) -> WasmEdgeResult<u32> {
+        self.len
+            .checked_mul(std::mem::size_of::<T>() as u32)
+            .ok_or_else(|| self.out_of_bounds())
+    }
+
+    pub fn get(&self, mem: &GuestMemory, idx: u32) -> WasmEdgeResult<T> {
+        mem.read(self.element_offset(idx)?)
+    }
+
+    pub fn set(&self, mem: &mut GuestMemory, idx: u32, val: T) -> WasmEdgeResult<()> {
+        mem.write(self.element_offset(idx)?, val)
+    }
+
+    pub fn read_all(&self, mem: &GuestMemory) -> WasmEdgeResult<Vec<T>> {
+        (0..self.len).map(|idx| self.get(mem, idx)).collect()
+    }
+
+    fn element_offset(&self, idx: u32) -> WasmEdgeResult<u32> {
+        if idx >= self.len {
+            return Err(WasmEdgeError::Operation(format!(
+                "slice index {} out of range for length {}",
+                idx, self.len
+            )));
+        }
+        idx.checked_mul(std::mem::size_of::<T>() as u32)
+            .and_then(|offset| self.offset.checked_add(offset))
+            .ok_or_else(|| self.out_of_bounds())
+    }
+
+    fn out_of_bounds(&self) -> WasmEdgeError {
+        WasmEdgeError::Operation(format!(
+            "guest memory access out of bounds: {} elements at {}",
+            self.len, self.offset
+        ))
+    }
+}
+
+impl WasmSlice<u8> {
+    pub fn read_bytes(&self, mem: &GuestMemory) -> WasmEdgeResult<Vec<u8>> {
+        mem.read_bytes(self.offset, self.len)
+    }
+
+    /// Writes `data` to the start of the slice, which must fit in it.
+    pub fn write_bytes(&self, mem: &mut GuestMemory, data: &[u8]) -> WasmEdgeResult<()> {
+        if data.len() > self.len as usize {
+            return Err(WasmEdgeError::Operation(format!(
+                "{} bytes do not fit in a slice of length {}",
+                data.len(),
+                self.len
+            )));
+        }
+        mem.write_bytes(self.offset, data)
+    }
+}
+
+/// A UTF-8 string in guest memory, passed as an `i32` pointer followed by an `i32` byte length.
+#[derive(Debug, Clone, Copy)]
+pub struct WasmStr {
+    offset: u32,
+    len: u32,
+}
+
+impl WasmStr {
+    pub fn new(offset: u32, len: u32) -> Self {
+        WasmStr { offset, len }
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn read(&self, mem: &GuestMemory) -> WasmEdgeResult<String> {
+        mem.read_str(self.offset, self.len)
+    }
+}
+
+/// A single typed host function argument, made of one or more wasm values.
+pub trait WasmArg: Sized {
+    fn val_types() -> Vec<ValType>;
+
+    /// Converts the leading values of `vals`, returning the argument and the number of values used.
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<(Self, usize)>;
+
+    /// Checks that the argument fits in a default memory of `mem_size` bytes.
+    fn in_bounds(&self, _mem_size: usize) -> bool {
+        true
+    }
+}
+
+fn range_in_bounds(offset: u32, len: usize, mem_size: usize) -> bool {
+    match (offset as usize).checked_add(len) {
+        Some(end) => end <= mem_size,
+        None => false,
+    }
+}
+
+macro_rules! impl_wasm_arg {
+    ($($t:ty => $val:ident),*) => {
+        $(impl WasmArg for $t {
+            fn val_types() -> Vec<ValType> {
+                vec![ValType::$val]
+            }
+
+            fn from_wasm_vals(vals: &[WasmVal]) -> Option<(Self, usize)> {
+                match vals.first() {
+                    Some(WasmVal::$val(v)) => Some((*v, 1)),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+impl_wasm_arg!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
+
+impl<T: Pod> WasmArg for WasmPtr<T> {
+    fn val_types() -> Vec<ValType> {
+        vec![ValType::I32]
+    }
+
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<(Self, usize)> {
+        match vals.first() {
+            Some(WasmVal::I32(offset)) => Some((WasmPtr::new(*offset as u32), 1)),
+            _ => None,
+        }
+    }
+
+    fn in_bounds(&self, mem_size: usize) -> bool {
+        range_in_bounds(self.offset, std::mem::size_of::<T>(), mem_size)
+    }
+}
+
+impl<T: Pod> WasmArg for WasmSlice<T> {
+    fn val_types() -> Vec<ValType> {
+        vec![ValType::I32, ValType::I32]
+    }
+
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<(Self, usize)> {
+        match vals {
+            [WasmVal::I32(offset), WasmVal::I32(len), ..] => {
+                Some((WasmSlice::new(*offset as u32, *len as u32), 2))
+            }
+            _ => None,
+        }
+    }
+
+    fn in_bounds(&self, mem_size: usize) -> bool {
+        match (self.len as usize).checked_mul(std::mem::size_of::<T>()) {
+            Some(byte_len) => range_in_bounds(self.offset, byte_len, mem_size),
+            None => false,
+        }
+    }
+}
+
+impl WasmArg for WasmStr {
+    fn val_types() -> Vec<ValType> {
+        vec![ValType::I32, ValType::I32]
+    }
+
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<(Self, usize)> {
+        match vals {
+            [WasmVal::I32(offset), WasmVal::I32(len), ..] => {
+                Some((WasmStr::new(*offset as u32, *len as u32), 2))
+            }
+            _ => None,
+        }
+    }
+
+    fn in_bounds(&self, mem_size: usize) -> bool {
+        range_in_bounds(self.offset, self.len as usize, mem_size)
+    }
+}
+
+/// The full argument list of a host function.
+///
+/// Implemented for tuples of [WasmArg]s, and for `Vec<WasmVal>` which passes the raw values through.
+pub trait WasmArgs: Sized {
+    /// The parameter types, or `None` if they are given when registering the function.
+    fn val_types() -> Option<Vec<ValType>>;
+
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<Self>;
+
+    fn in_bounds(&self, _mem_size: usize) -> bool {
+        true
+    }
+}
+
+impl WasmArgs for Vec<WasmVal> {
+    fn val_types() -> Option<Vec<ValType>> {
+        None
+    }
+
+    fn from_wasm_vals(vals: &[WasmVal]) -> Option<Self> {
+        Some(vals.to_vec())
+    }
+}
+
+macro_rules! impl_wasm_args {
+    ($($name:ident),*) => {
+        impl<$($name: WasmArg),*> WasmArgs for ($($name,)*) {
+            fn val_types() -> Option<Vec<ValType>> {
+                #[allow(unused_mut)]
+                let mut types = vec![];
+                $(types.extend($name::val_types());)*
+                Some(types)
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn from_wasm_vals(vals: &[WasmVal]) -> Option<Self> {
+                let mut rest = vals;
+                $(
+                    let ($name, used) = $name::from_wasm_vals(rest)?;
+                    rest = &rest[used..];
+                )*
+                if !rest.is_empty() {
+                    return None;
+                }
+                Some(($($name,)*))
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            fn in_bounds(&self, mem_size: usize) -> bool {
+                let ($($name,)*) = self;
+                true $(&& $name.in_bounds(mem_size))*
+            }
+        }
+    };
+}
+impl_wasm_args!();
+impl_wasm_args!(A);
+impl_wasm_args!(A, B);
+impl_wasm_args!(A, B, C);
+impl_wasm_args!(A, B, C, D);
+impl_wasm_args!(A, B, C, D, E);
+impl_wasm_args!(A, B, C, D, E, F);
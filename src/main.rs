@@ -86,10 +86,7 @@ fn linker_sleep1(linker: Option<&mut Linker>, _: &[WasmVal]) -> Result<Vec<WasmV
 fn try_asyncify() {
     println!("Hello, world!");
     let wasm = pass_and_load_wasm("demo2.wasm");
-    let mut config = Config::create().unwrap();
-    config.bulk_memory_operations(true);
-    config.multi_memories(true);
-    let config = Some(config);
+    let config = Some(Config::for_module(&wasm).unwrap());
 
     let loader = Loader::create(&config).unwrap();
     let ast_module = loader.load_module_from_bytes(&wasm).unwrap();
@@ -149,15 +146,9 @@ fn main() {
 
     let loader = Loader::create(&config).unwrap();
 
-    // let wasm = std::fs::read("demo2.wasm").unwrap();
-
-    let wasm = std::fs::read("wasm_main.wasm").unwrap();
-
     let ast_module = loader
-        .load_async_module_from_bytes(&wasm, &["spectest.sleep*"])
+        .load_async_from_file("wasm_main.wasm", &["spectest.sleep*"])
         .unwrap();
 
-    // let ast_module = loader.load_module_from_bytes(&wasm).unwrap();
-
     async_sdk::async_mod::try_(&config, ast_module);
 }